@@ -2,24 +2,31 @@ use std::collections::HashMap;
 
 pub mod cell;
 pub mod dep_store;
+pub mod format;
 pub mod range;
 
 use cell::{Cell, CellData};
 
 use dep_store::DepStore;
+use format::NumberFormat;
 use range::{DependencyData, DependencyObject};
 
 /// Struct for database
 /// Data is stored in a hashmap that maps cell index to the cell struct for that cell
 /// num_rows: total no of rows in the database
 /// num_cols: total no of columns in the database
-
+/// col_widths: display widths of the columns that were resized
+/// formats: number formats of the cells that have one
+/// dirty: set by every change since the sheet was last saved or loaded
 pub struct Database {
     pub num_rows: u16,
     pub num_cols: u16,
     store: HashMap<u32, Cell>,
     range_deps: DepStore,
     point_deps: HashMap<u32, Vec<u32>>,
+    col_widths: HashMap<u32, u16>,
+    formats: HashMap<u32, NumberFormat>,
+    dirty: bool,
 }
 
 impl Database {
@@ -31,12 +38,26 @@ impl Database {
             store: HashMap::new(),
             range_deps: DepStore::new(),
             point_deps: HashMap::new(),
+            col_widths: HashMap::new(),
+            formats: HashMap::new(),
+            dirty: false,
         }
     }
 
+    /// Checks if the sheet changed since it was last saved or loaded
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Clears the dirty flag, after the sheet was saved or loaded
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
     /// Gets the data at cell represented by cell_idx
     /// returns Err(false) if cell out of range
     /// else returns Err(true) if cell has cell.error set as true
+    /// Cells that are not in the store return CellData::Empty
     pub fn get(&self, cell_idx: u32) -> Result<&CellData, bool> {
         if !self.cell_in_range(cell_idx) {
             return Err(false);
@@ -45,7 +66,7 @@ impl Database {
         let cell = self.store.get(&cell_idx);
         let cell = match cell {
             Some(cell) => cell,
-            None => return Ok(&CellData::IntData(0)),
+            None => return Ok(&CellData::Empty),
         };
 
         let data = cell.get_data();
@@ -87,6 +108,7 @@ impl Database {
         if !self.cell_in_range(cell_idx) {
             return Err(false);
         }
+        self.dirty = true;
 
         let cell = self.store.get_mut(&cell_idx);
         match cell {
@@ -99,6 +121,7 @@ impl Database {
         if !self.cell_in_range(cell_idx) {
             return Err(());
         }
+        self.dirty = true;
 
         let cell = self.store.get_mut(&cell_idx);
         let cell = match cell {
@@ -116,6 +139,7 @@ impl Database {
         if !self.cell_in_range(cell_idx) {
             return Result::Err(());
         }
+        self.dirty = true;
 
         let cell = self.store.get_mut(&cell_idx);
         let cell = match cell {
@@ -129,6 +153,23 @@ impl Database {
         return Ok(());
     }
 
+    /// Removes the cell from the store, leaving it empty
+    /// Cells depending on it stay registered as its children
+    pub fn remove(&mut self, cell_idx: u32) -> Result<(), ()> {
+        if !self.cell_in_range(cell_idx) {
+            return Err(());
+        }
+        self.dirty = true;
+
+        self.store.remove(&cell_idx);
+        Ok(())
+    }
+
+    /// Returns the indices of all cells in the store, in no particular order
+    pub fn initialized_cells(&self) -> Vec<u32> {
+        self.store.keys().copied().collect()
+    }
+
     pub fn is_cell_initialized(&self, cell_idx: u32) -> Result<bool, ()> {
         if !self.cell_in_range(cell_idx) {
             return Err(());
@@ -149,6 +190,7 @@ impl Database {
         if !self.cell_in_range(cell_idx) {
             return Err(());
         }
+        self.dirty = true;
 
         let cell = self.store.get_mut(&cell_idx);
         let cell = match cell {
@@ -182,6 +224,7 @@ impl Database {
     }
 
     pub fn add_dep_point(&mut self, dep: u32, target: u32) {
+        self.dirty = true;
         match self.point_deps.get_mut(&dep) {
             Some(v) => {
                 v.push(target);
@@ -193,6 +236,7 @@ impl Database {
     }
 
     pub fn rem_dep_point(&mut self, dep: u32, target: u32) {
+        self.dirty = true;
         match self.point_deps.get_mut(&dep) {
             Some(v) => {
                 v.retain(|&val| val != target);
@@ -202,10 +246,12 @@ impl Database {
     }
 
     pub fn add_dep_range(&mut self, dep: DependencyObject) {
+        self.dirty = true;
         self.range_deps.insert(dep);
     }
 
     pub fn rem_dep_range(&mut self, cell_idx: u32, dep: DependencyData) {
+        self.dirty = true;
         self.range_deps
             .remove(DependencyObject::from_dep_data(cell_idx, dep));
     }
@@ -225,6 +271,60 @@ impl Database {
         deps.len() + pdeps_len == 0
     }
 
+    /// Gets the display width of col, None if it was never resized
+    pub fn get_col_width(&self, col: u32) -> Option<u16> {
+        self.col_widths.get(&col).copied()
+    }
+
+    /// Sets the display width of col, None resets it to the default width
+    pub fn set_col_width(&mut self, col: u32, width: Option<u16>) {
+        self.dirty = true;
+        match width {
+            Some(width) => self.col_widths.insert(col, width),
+            None => self.col_widths.remove(&col),
+        };
+    }
+
+    /// Returns the (column, width) of every resized column, ordered by column
+    pub fn col_widths(&self) -> Vec<(u32, u16)> {
+        let mut widths: Vec<(u32, u16)> = self.col_widths.iter().map(|(&c, &w)| (c, w)).collect();
+        widths.sort();
+        widths
+    }
+
+    /// Gets the number format of the cell, None for the default display
+    pub fn get_format(&self, cell_idx: u32) -> Option<NumberFormat> {
+        self.formats.get(&cell_idx).copied()
+    }
+
+    /// Sets the number format of the cell, None resets it to the default display
+    /// A cell keeps its format when it is emptied
+    pub fn set_format(&mut self, cell_idx: u32, format: Option<NumberFormat>) {
+        self.dirty = true;
+        match format {
+            Some(format) => self.formats.insert(cell_idx, format),
+            None => self.formats.remove(&cell_idx),
+        };
+    }
+
+    /// Returns the (cell index, format) of every formatted cell, ordered row by row
+    pub fn formats(&self) -> Vec<(u32, NumberFormat)> {
+        let mut formats: Vec<(u32, NumberFormat)> =
+            self.formats.iter().map(|(&c, &f)| (c, f)).collect();
+        formats.sort_by_key(|&(c, _)| (c % 1000, c / 1000));
+        formats
+    }
+
+    /// Displayed text of a cell: its value in its number format, or the default display
+    /// Err(true) if the cell has an error, Err(false) if it is out of range
+    pub fn get_formatted(&self, cell_idx: u32) -> Result<String, bool> {
+        let data = self.get(cell_idx)?;
+        Ok(match self.get_format(cell_idx) {
+            Some(format) => format.apply(data),
+            None => data.to_string(),
+        })
+    }
+
     pub fn get_cell_children(&self, cell_idx: u32) -> Vec<u32> {
         let mut range_dep: Vec<u32> = self
             .range_deps
@@ -3,10 +3,13 @@ use std::mem;
 use std::ops::{Add, Div, Mul, Sub};
 
 /// Enum for different types of data that a spreadsheet cell can store
+/// Empty is the data of a cell that was never set or was cleared, it is never stored in a Cell
+/// Arithmetic treats Empty as 0, range functions skip it
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CellData {
     IntData(i32),
     FloatData(f32),
+    Empty,
 }
 
 /// Struct to store data of a cell
@@ -20,7 +23,7 @@ pub struct Cell {
 impl CellData {
     pub fn to_int(&self) -> CellData {
         match self {
-            CellData::IntData(_) => self.clone(),
+            CellData::IntData(_) | CellData::Empty => *self,
             CellData::FloatData(f) => CellData::IntData(*f as i32),
         }
     }
@@ -31,6 +34,7 @@ impl std::fmt::Display for CellData {
         match self {
             CellData::IntData(i) => write!(f, "{}", i),
             CellData::FloatData(fl) => write!(f, "{:.2}", fl), // format to 2 decimal places
+            CellData::Empty => Ok(()),
         }
     }
 }
@@ -44,6 +48,8 @@ impl Add for CellData {
             (CellData::IntData(a), CellData::FloatData(b)) => CellData::FloatData(a as f32 + b),
             (CellData::FloatData(a), CellData::IntData(b)) => CellData::FloatData(a + b as f32),
             (CellData::FloatData(a), CellData::FloatData(b)) => CellData::FloatData(a + b),
+            (CellData::Empty, b) => CellData::IntData(0) + b,
+            (a, CellData::Empty) => a + CellData::IntData(0),
         }
     }
 }
@@ -57,6 +63,8 @@ impl Sub for CellData {
             (CellData::IntData(a), CellData::FloatData(b)) => CellData::FloatData(a as f32 - b),
             (CellData::FloatData(a), CellData::IntData(b)) => CellData::FloatData(a - b as f32),
             (CellData::FloatData(a), CellData::FloatData(b)) => CellData::FloatData(a - b),
+            (CellData::Empty, b) => CellData::IntData(0) - b,
+            (a, CellData::Empty) => a - CellData::IntData(0),
         }
     }
 }
@@ -70,6 +78,8 @@ impl Mul for CellData {
             (CellData::IntData(a), CellData::FloatData(b)) => CellData::FloatData(a as f32 * b),
             (CellData::FloatData(a), CellData::IntData(b)) => CellData::FloatData(a * b as f32),
             (CellData::FloatData(a), CellData::FloatData(b)) => CellData::FloatData(a * b),
+            (CellData::Empty, b) => CellData::IntData(0) * b,
+            (a, CellData::Empty) => a * CellData::IntData(0),
         }
     }
 }
@@ -88,6 +98,8 @@ impl Div for CellData {
             (CellData::IntData(a), CellData::FloatData(b)) => Ok(CellData::FloatData(a as f32 / b)),
             (CellData::FloatData(a), CellData::IntData(b)) => Ok(CellData::FloatData(a / b as f32)),
             (CellData::FloatData(a), CellData::FloatData(b)) => Ok(CellData::FloatData(a / b)),
+            (CellData::Empty, b) => CellData::IntData(0) / b,
+            (a, CellData::Empty) => a / CellData::IntData(0),
         }
     }
 }
@@ -384,6 +396,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_celldata_empty() {
+        assert_eq!(format!("{}", CellData::Empty), "", "Empty display failed");
+        assert!(
+            matches!(CellData::Empty.to_int(), CellData::Empty),
+            "Empty to Int failed"
+        );
+        assert!(
+            matches!(CellData::Empty + CellData::IntData(3), CellData::IntData(3)),
+            "Empty + Int failed"
+        );
+        assert!(
+            matches!(
+                CellData::FloatData(2.5) - CellData::Empty,
+                CellData::FloatData(2.5)
+            ),
+            "Float - Empty failed"
+        );
+        assert!(
+            matches!(CellData::Empty * CellData::Empty, CellData::IntData(0)),
+            "Empty * Empty failed"
+        );
+        assert!(
+            matches!(CellData::IntData(4) / CellData::Empty, Err(())),
+            "Int / Empty failed"
+        );
+        assert!(
+            matches!(
+                CellData::Empty / CellData::IntData(4),
+                Ok(CellData::IntData(0))
+            ),
+            "Empty / Int failed"
+        );
+    }
+
     #[test]
     fn test_cell_creation() {
         let data = CellData::IntData(100);
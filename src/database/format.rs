@@ -0,0 +1,157 @@
+use super::cell::CellData;
+
+/// Display format of a number, written as a pattern like the ones of other spreadsheets
+/// "0" integer, "0.000" fixed decimals, "#,##0.00" thousands separators,
+/// "0.0%" percentage, "$#,##0.00" currency, "0.00E+00" scientific
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    decimals: usize,
+    thousands: bool,
+    percent: bool,
+    scientific: bool,
+    currency: Option<char>,
+}
+
+impl NumberFormat {
+    /// Parses a pattern, see NumberFormat
+    /// Returns None if the pattern is not valid
+    pub fn parse(pattern: &str) -> Option<NumberFormat> {
+        let mut s = pattern;
+
+        let currency = s
+            .chars()
+            .next()
+            .filter(|c| ['$', '€', '£', '¥'].contains(c));
+        if let Some(c) = currency {
+            s = &s[c.len_utf8()..];
+        }
+
+        let percent = s.ends_with('%');
+        s = s.trim_end_matches('%');
+
+        let scientific = s.ends_with("E+00");
+        s = s.trim_end_matches("E+00");
+
+        let (int_part, dec_part) = s.split_once('.').unwrap_or((s, ""));
+        if !int_part.ends_with('0')
+            || !int_part.chars().all(|c| ['#', ',', '0'].contains(&c))
+            || !dec_part.chars().all(|c| c == '0')
+            || (s.contains('.') && dec_part.is_empty())
+        {
+            return None;
+        }
+
+        Some(NumberFormat {
+            decimals: dec_part.len(),
+            thousands: int_part.contains(','),
+            percent,
+            scientific,
+            currency,
+        })
+    }
+
+    /// Formats data, empty cells stay empty
+    pub fn apply(&self, data: &CellData) -> String {
+        let mut value = match data {
+            CellData::IntData(i) => *i as f64,
+            CellData::FloatData(f) => *f as f64,
+            CellData::Empty => return String::new(),
+        };
+        if self.percent {
+            value *= 100.0;
+        }
+
+        let mut digits = if self.scientific {
+            // Rust writes 1.5e3, spreadsheets write 1.5E+03
+            let s = format!("{:.*e}", self.decimals, value.abs());
+            let (mantissa, exp) = s.split_once('e').unwrap_or((&s, "0"));
+            let exp: i32 = exp.parse().unwrap_or(0);
+            format!(
+                "{}E{}{:02}",
+                mantissa,
+                if exp < 0 { '-' } else { '+' },
+                exp.abs()
+            )
+        } else {
+            format!("{:.*}", self.decimals, value.abs())
+        };
+
+        if self.thousands && !self.scientific {
+            let int_len = digits.find('.').unwrap_or(digits.len());
+            let mut grouped = String::new();
+            for (i, c) in digits[..int_len].chars().enumerate() {
+                if i > 0 && (int_len - i) % 3 == 0 {
+                    grouped.push(',');
+                }
+                grouped.push(c);
+            }
+            digits = grouped + &digits[int_len..];
+        }
+
+        // Values rounding to zero are not shown as negative
+        let negative = value < 0.0 && digits.chars().any(|c| c.is_ascii_digit() && c != '0');
+        format!(
+            "{}{}{}{}",
+            if negative { "-" } else { "" },
+            self.currency.map(String::from).unwrap_or_default(),
+            digits,
+            if self.percent { "%" } else { "" }
+        )
+    }
+}
+
+impl std::fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(c) = self.currency {
+            write!(f, "{}", c)?;
+        }
+        write!(f, "{}", if self.thousands { "#,##0" } else { "0" })?;
+        if self.decimals > 0 {
+            write!(f, ".{}", "0".repeat(self.decimals))?;
+        }
+        if self.scientific {
+            write!(f, "E+00")?;
+        }
+        if self.percent {
+            write!(f, "%")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(pattern: &str, data: CellData) -> String {
+        NumberFormat::parse(pattern).unwrap().apply(&data)
+    }
+
+    #[test]
+    fn test_parse() {
+        for pattern in ["0", "0.000", "#,##0.00", "0.0%", "$#,##0.00", "0.00E+00"] {
+            assert_eq!(NumberFormat::parse(pattern).unwrap().to_string(), pattern);
+        }
+        assert!(NumberFormat::parse("").is_none());
+        assert!(NumberFormat::parse("0.").is_none());
+        assert!(NumberFormat::parse("abc").is_none());
+        assert!(NumberFormat::parse("0.0#").is_none());
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(fmt("0", CellData::FloatData(2.6)), "3");
+        assert_eq!(fmt("0.000", CellData::IntData(7)), "7.000");
+        assert_eq!(
+            fmt("#,##0.00", CellData::IntData(-1234567)),
+            "-1,234,567.00"
+        );
+        assert_eq!(fmt("#,##0", CellData::IntData(999)), "999");
+        assert_eq!(fmt("0.0%", CellData::FloatData(0.125)), "12.5%");
+        assert_eq!(fmt("$#,##0.00", CellData::FloatData(-1500.5)), "-$1,500.50");
+        assert_eq!(fmt("0.00E+00", CellData::IntData(12345)), "1.23E+04");
+        assert_eq!(fmt("0.0E+00", CellData::FloatData(-0.00025)), "-2.5E-04");
+        assert_eq!(fmt("0.0", CellData::FloatData(-0.01)), "0.0");
+        assert_eq!(fmt("0.0", CellData::Empty), "");
+    }
+}
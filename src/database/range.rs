@@ -51,6 +51,30 @@ impl DependencyData {
     pub fn get_post(&self) -> DependencyNums {
         self.post
     }
+
+    /// Returns the dependency with every cell reference moved by drow rows and dcol columns
+    /// Returns None if a reference would fall before the first row/column or past row 1000
+    pub fn shifted(&self, drow: i64, dcol: i64) -> Option<DependencyData> {
+        fn shift(num: DependencyNums, drow: i64, dcol: i64) -> Option<DependencyNums> {
+            match num {
+                DependencyNums::U32(c) => {
+                    let row = (c % 1000) as i64 + drow;
+                    let col = (c / 1000) as i64 + dcol;
+                    if !(0..1000).contains(&row) || col < 0 {
+                        return None;
+                    }
+                    Some(DependencyNums::U32((1000 * col + row) as u32))
+                }
+                _ => Some(num),
+            }
+        }
+
+        Some(DependencyData {
+            oper: self.oper,
+            pre: shift(self.pre, drow, dcol)?,
+            post: shift(self.post, drow, dcol)?,
+        })
+    }
 }
 
 impl DependencyObject {
@@ -22,6 +22,39 @@ pub fn generate_column_label(mut col_index: u32) -> String {
     label.chars().rev().collect()
 }
 
+///function column_index :
+///returns the integer for a column label,
+///the inverse of generate_column_label
+///(eg. "A" -> 0, "AA" -> 26, "a1" -> None)
+///
+pub fn column_index(label: &str) -> Option<u32> {
+    if label.is_empty() || label.len() > 3 || !label.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+
+    let idx = label
+        .bytes()
+        .fold(0, |acc, c| 26 * acc + (c - b'A') as u32 + 1);
+    Some(idx - 1)
+}
+
+///function cell_index :
+///returns the zero based (row, col) of a cell label
+///(eg. "A1" -> (0, 0), "B250" -> (249, 1), "B0" -> None)
+///
+pub fn cell_index(label: &str) -> Option<(u32, u32)> {
+    let split = label.find(|c: char| c.is_ascii_digit())?;
+    let col = column_index(&label[..split])?;
+
+    let digits = &label[split..];
+    if digits.starts_with('0') || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let row = digits.parse::<u32>().ok()?;
+
+    Some((row - 1, col))
+}
+
 #[cfg(not(tarpaulin_include))]
 ///function print_spreadsheet :
 ///prints the spreadsheet
@@ -58,7 +91,10 @@ pub fn print_spreadsheet(db: &Database, top_left_cell: u32) {
             match db.get(1000 * c + r) {
                 Ok(&ref d) => print!(
                     "{:>width$}",
-                    d.to_int().to_string(),
+                    match db.get_format(1000 * c + r) {
+                        Some(format) => format.apply(d),
+                        None => d.to_int().to_string(),
+                    },
                     width = CELL_WIDTH as usize
                 ),
                 Err(true) => print!("{:>width$}", "ERR", width = CELL_WIDTH as usize),
@@ -77,5 +113,18 @@ mod tests {
     fn test_col_label() {
         assert!(generate_column_label(0) == "A");
         assert!(generate_column_label(26) == "AA");
+
+        assert!(column_index("A") == Some(0));
+        assert!(column_index("AA") == Some(26));
+        assert!(column_index("ZZZ") == Some(18277));
+        assert!(column_index("a").is_none());
+        assert!(column_index("").is_none());
+
+        assert!(cell_index("A1") == Some((0, 0)));
+        assert!(cell_index("B250") == Some((249, 1)));
+        assert!(cell_index("B0").is_none());
+        assert!(cell_index("B").is_none());
+        assert!(cell_index("12").is_none());
+        assert!(cell_index("B2x").is_none());
     }
 }
@@ -1,7 +1,8 @@
 use crate::database::cell::{Cell, CellData};
 use crate::database::range::{DependencyData, DependencyNums, DependencyObject};
 use crate::database::Database;
-use crate::parser::Response;
+use crate::display;
+use crate::parser::{self, Response};
 use crate::utils;
 use core::{f32, panic};
 use std::thread::sleep;
@@ -12,11 +13,11 @@ use std::thread::sleep;
 ///
 
 fn evaluate(db: &mut Database, cell_idx: u32) {
-    let target;
-    if let Ok(cell) = db.get_cell_mut(cell_idx) {
-        target = cell
-    } else {
-        panic!()
+    let target = match db.get_cell_mut(cell_idx) {
+        Ok(cell) => cell,
+        // Cleared cells have nothing to evaluate
+        Err(true) => return,
+        Err(false) => panic!(),
     };
 
     if !target.has_dep() {
@@ -214,6 +215,7 @@ fn min_fn(db: &mut Database, cell_idx: u32) {
                 Ok(d) => match d {
                     CellData::IntData(i) => *i as f32,
                     CellData::FloatData(f) => *f,
+                    CellData::Empty => continue,
                 },
                 Err(true) => {
                     let target;
@@ -235,6 +237,11 @@ fn min_fn(db: &mut Database, cell_idx: u32) {
         }
     }
 
+    // A range of empty cells has a minimum of 0
+    if min_val == f32::MAX {
+        min_val = 0.0;
+    }
+
     let target;
     if let Ok(cell) = db.get_cell_mut(cell_idx) {
         target = cell
@@ -284,6 +291,7 @@ fn max_fn(db: &mut Database, cell_idx: u32) {
                 Ok(d) => match d {
                     CellData::IntData(i) => *i as f32,
                     CellData::FloatData(f) => *f,
+                    CellData::Empty => continue,
                 },
                 Err(true) => {
                     let target;
@@ -305,6 +313,11 @@ fn max_fn(db: &mut Database, cell_idx: u32) {
         }
     }
 
+    // A range of empty cells has a maximum of 0
+    if max_val == f32::MIN {
+        max_val = 0.0;
+    }
+
     let target;
     if let Ok(cell) = db.get_cell_mut(cell_idx) {
         target = cell
@@ -330,6 +343,7 @@ fn avg_fn(db: &mut Database, cell_idx: u32) {
         Ok(data) => match data {
             CellData::FloatData(f) => *f,
             CellData::IntData(i) => *i as f32,
+            CellData::Empty => panic!(),
         },
         Err(true) => {
             return;
@@ -355,12 +369,13 @@ fn avg_fn(db: &mut Database, cell_idx: u32) {
         panic!();
     }
 
-    let row_low = pre % 1000;
-    let row_high = post % 1000;
-    let col_low = pre / 1000;
-    let col_high = post / 1000;
-
-    avg /= ((row_high - row_low + 1) * (col_high - col_low + 1)) as f32;
+    // Empty cells are not counted, the average of an empty range is an error
+    let count = count_values(db, pre, post);
+    if count == 0 {
+        let _ = db.set_error(cell_idx, true);
+        return;
+    }
+    avg /= count as f32;
 
     let target;
     if let Ok(cell) = db.get_cell_mut(cell_idx) {
@@ -373,6 +388,22 @@ fn avg_fn(db: &mut Database, cell_idx: u32) {
     target.set_error(false);
 }
 
+///function count_values : counts the cells
+///of the range pre:post that are not empty
+///
+fn count_values(db: &Database, pre: u32, post: u32) -> u32 {
+    let mut count = 0;
+    for col in pre / 1000..=post / 1000 {
+        for row in pre % 1000..=post % 1000 {
+            if let Ok(CellData::Empty) = db.get(1000 * col + row) {
+                continue;
+            }
+            count += 1;
+        }
+    }
+    count
+}
+
 ///function sum_fn : given a cell
 ///(having updates dependencies) and a database
 ///evaluate the cell data to sum of
@@ -411,6 +442,7 @@ fn sum_fn(db: &mut Database, cell_idx: u32) {
                 Ok(d) => match d {
                     CellData::IntData(i) => *i as f32,
                     CellData::FloatData(f) => *f,
+                    CellData::Empty => continue,
                 },
                 Err(true) => {
                     let target;
@@ -495,6 +527,7 @@ fn stdev_fn(db: &mut Database, cell_idx: u32) {
                 Ok(d) => match d {
                     CellData::IntData(i) => *i as f32,
                     CellData::FloatData(f) => *f,
+                    CellData::Empty => continue,
                 },
                 Err(true) => {
                     let target;
@@ -515,7 +548,7 @@ fn stdev_fn(db: &mut Database, cell_idx: u32) {
             var += (data - avg).powi(2);
         }
 
-        var /= ((row_high - row_low + 1) * (col_high - col_low + 1)) as f32;
+        var /= count_values(db, pre, post) as f32;
     }
 
     let target;
@@ -570,6 +603,7 @@ fn sleep_fn(db: &mut Database, cell_idx: u32) {
                                     sleep(std::time::Duration::from_secs_f32(*f));
                                 }
                             }
+                            CellData::Empty => {}
                         }
                         target.set_data(*d);
                         target.set_error(false);
@@ -702,6 +736,34 @@ pub fn evaluator(
         return 0;
     }
 
+    // Command: clear
+    if r.func == 21 {
+        return clear_range(db, (r.target - 1001) as u32, (r.arg1 - 1001) as u32);
+    }
+
+    match register(r, db) {
+        Ok(topo_order) => {
+            for cell in topo_order {
+                evaluate(db, cell);
+            }
+        }
+        Err(ec) => return ec,
+    }
+
+    // Fallback return, if none of the commands match
+    0
+}
+
+///function register : given a parsed assignment, replaces the
+///dependencies of the target cell without evaluating anything
+///returns the topological order of the cells to recalculate,
+///or the error code if the assignment is rejected
+///
+fn register(r: Response, db: &mut Database) -> Result<Vec<u32>, i32> {
+    if !db.cell_in_range((r.target - 1001) as u32) {
+        return Err(4);
+    }
+
     if let Ok(init) = db.is_cell_initialized((r.target - 1001) as u32) {
         if !init {
             let _ = db.set_int((r.target - 1001) as u32, 0);
@@ -715,7 +777,7 @@ pub fn evaluator(
     if (r.arg_type & 2 != 0 && !db.cell_in_range((r.arg1 - 1001) as u32))
         || (r.arg_type & 1 != 0 && !db.cell_in_range((r.arg2 - 1001) as u32))
     {
-        return 4;
+        return Err(4);
     }
 
     // Capture state of the cell before modification
@@ -924,17 +986,127 @@ pub fn evaluator(
             }
         }
 
-        return 3;
+        return Err(3);
     }
 
-    for cell in topo_order {
-        evaluate(db, cell);
+    Ok(topo_order)
+}
+
+///function assign : given a parsed assignment, updates the
+///target cell and its dependencies without recalculating,
+///so that several cells can be changed before one call to recalc
+///returns the error code of the assignment (0 if ok)
+///
+pub fn assign(r: Response, db: &mut Database) -> i32 {
+    if r.status != 0 {
+        return r.status;
     }
 
-    // Fallback return, if none of the commands match
+    if !(1..=12).contains(&r.func) {
+        return 1;
+    }
+
+    match register(r, db) {
+        Ok(_) => 0,
+        Err(ec) => ec,
+    }
+}
+
+///function assign_text : given user input for a cell, either a
+///number or a formula starting with '=', updates the cell without
+///recalculating, like assign
+///returns the error code of the assignment (0 if ok)
+///
+pub fn assign_text(db: &mut Database, cell_idx: u32, text: &str) -> i32 {
+    let mut cell = display::generate_column_label(cell_idx / 1000);
+    cell += &(cell_idx % 1000 + 1).to_string();
+
+    if let Some(formula) = text.strip_prefix('=') {
+        return assign(parser::parse(&format!("{}={}", cell, formula)), db);
+    }
+
+    if text.parse::<i32>().is_ok() {
+        return assign(parser::parse(&format!("{}={}", cell, text)), db);
+    }
+
+    match text.parse::<f32>() {
+        Ok(f) => assign_data(db, cell_idx, CellData::FloatData(f)),
+        Err(_) => 1,
+    }
+}
+
+///function assign_data : replaces the contents of a cell with
+///a constant, dropping its formula, without recalculating
+///used for values the parser cannot express (e.g. floats)
+///
+pub fn assign_data(db: &mut Database, cell_idx: u32, data: CellData) -> i32 {
+    if !db.cell_in_range(cell_idx) {
+        return 4;
+    }
+
+    let old_dep = db.get_cell_parent_dep(cell_idx);
+    utils::rem_dep(db, old_dep, cell_idx);
+    db.rem_cell_parent_dep(cell_idx);
+
+    let _ = match data {
+        CellData::IntData(i) => db.set_int(cell_idx, i),
+        CellData::FloatData(f) => db.set_float(cell_idx, f),
+        CellData::Empty => db.remove(cell_idx),
+    };
+    let _ = db.set_error(cell_idx, false);
     0
 }
 
+///function clear : empties a cell, dropping its data and
+///formula, without recalculating the cells depending on it
+///
+pub fn clear(db: &mut Database, cell_idx: u32) -> i32 {
+    if !db.cell_in_range(cell_idx) {
+        return 4;
+    }
+
+    let old_dep = db.get_cell_parent_dep(cell_idx);
+    utils::rem_dep(db, old_dep, cell_idx);
+    let _ = db.remove(cell_idx);
+    0
+}
+
+///function clear_range : empties every cell of the range
+///start:end and recalculates the cells depending on them
+///
+pub fn clear_range(db: &mut Database, start: u32, end: u32) -> i32 {
+    if !db.cell_in_range(start) || !db.cell_in_range(end) {
+        return 4;
+    }
+
+    let row_low = (start % 1000).min(end % 1000);
+    let row_high = (start % 1000).max(end % 1000);
+    let col_low = (start / 1000).min(end / 1000);
+    let col_high = (start / 1000).max(end / 1000);
+
+    let mut cleared = Vec::new();
+    for col in col_low..=col_high {
+        for row in row_low..=row_high {
+            clear(db, 1000 * col + row);
+            cleared.push(1000 * col + row);
+        }
+    }
+
+    recalc(db, &cleared);
+    0
+}
+
+///function recalc : evaluates the given cells and every cell
+///depending on them in a single topological pass
+///
+pub fn recalc(db: &mut Database, cells: &[u32]) {
+    if let Ok(topo_order) = utils::topological_sort_all(db, cells) {
+        for cell in topo_order {
+            evaluate(db, cell);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1181,6 +1353,78 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_clear() {
+        let mut db = Database::new(100, 100);
+        let mut state: (u32, bool, bool) = (0, true, true);
+
+        for cmd in ["A1=5", "A2=6", "B1=A1+1", "B2=SUM(A1:A2)", "A3=B1"] {
+            let r = parser::parse(cmd);
+            assert!(evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2) == 0);
+        }
+
+        let r = parser::parse("A1=");
+        let ec = evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2);
+        assert!(ec == 0);
+        assert!(db.is_cell_initialized(0) == Ok(false));
+        assert!(db.get(1000) == Ok(&CellData::IntData(1)));
+        assert!(db.get(1001) == Ok(&CellData::FloatData(6.0)));
+
+        // Clearing a formula cell unregisters it from its parents
+        let r = parser::parse("clear A3:B3");
+        let ec = evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2);
+        assert!(ec == 0);
+        assert!(db.is_cell_initialized(2) == Ok(false));
+        assert!(!db.get_cell_children(1000).contains(&2));
+
+        let r = parser::parse("A2=");
+        evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2);
+        assert!(db.get(1001) == Ok(&CellData::FloatData(0.0)));
+
+        let r = parser::parse("clear A1:Z200");
+        let ec = evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2);
+        assert!(ec == 4);
+    }
+
+    #[test]
+    fn test_empty_cells() {
+        let mut db = Database::new(100, 100);
+        let mut state: (u32, bool, bool) = (0, true, true);
+
+        assert!(db.get(0) == Ok(&CellData::Empty));
+
+        for cmd in [
+            "A1=4",
+            "A3=8",
+            "B1=AVG(A1:A4)",
+            "B2=STDEV(A1:A4)",
+            "B3=MIN(A1:A4)",
+            "B4=A2+1",
+            "C1=AVG(D1:D3)",
+            "C2=MAX(D1:D3)",
+            "C3=SUM(D1:D3)",
+        ] {
+            let r = parser::parse(cmd);
+            evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2);
+        }
+
+        // Blanks are skipped by range functions
+        assert!(db.get(1000) == Ok(&CellData::FloatData(6.0)));
+        assert!(db.get(1001) == Ok(&CellData::FloatData(2.0)));
+        assert!(db.get(1002) == Ok(&CellData::FloatData(4.0)));
+        // and treated as 0 by arithmetic
+        assert!(db.get(1003) == Ok(&CellData::IntData(1)));
+
+        assert!(db.get(2000) == Err(true));
+        assert!(db.get(2001) == Ok(&CellData::FloatData(0.0)));
+        assert!(db.get(2002) == Ok(&CellData::FloatData(0.0)));
+
+        // A zero is a value, unlike a blank
+        let r = parser::parse("A2=0");
+        evaluator(r, &mut db, &mut state.0, &mut state.1, &mut state.2);
+        assert!(db.get(1000) == Ok(&CellData::FloatData(4.0)));
+    }
+
     #[test]
     fn test_scroll() {
         let mut db = Database::new(100, 100);
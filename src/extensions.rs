@@ -1,20 +1,45 @@
 pub mod app;
+mod chart;
+mod clipboard;
 mod command_handler;
+mod complete;
+mod cond_format;
+mod config;
+mod fill;
+mod filter;
+mod help;
+mod history;
 mod input;
+mod keymap;
+mod native;
+mod search;
+mod sort;
+mod stats;
+mod svg;
+mod swap;
+mod theme;
 mod ui;
 
-use self::app::{App, AppCommand};
+use self::app::{App, AppCommand, Mode};
+use self::command_handler::autosave;
+use self::config::Config;
+use self::history::History;
 use self::input::handle_input;
+use self::swap::remove_swap;
 use self::ui::render;
 use crate::database::Database;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{self, DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use std::io::{stdout, Stdout};
+use std::time::Instant;
+
+/// History of commands and formulas, in the home directory
+const HISTORY_FILE: &str = ".spreadsheet_history";
 
 #[cfg(not(tarpaulin_include))]
 pub fn run_tui(db: Database) -> std::io::Result<()> {
@@ -26,15 +51,70 @@ pub fn run_tui(db: Database) -> std::io::Result<()> {
 
     let mut app = App::new(db);
 
+    // An invalid config is reported and the defaults are used
+    let config = match Config::path().map(|path| Config::load(&path)) {
+        Some(Ok(config)) => config,
+        Some(Err(err)) => {
+            app.log(&format!("Invalid config file, {}", err));
+            app.mode = Mode::ErrMsg("Invalid config file, using the defaults, see :messages");
+            Config::default()
+        }
+        None => Config::default(),
+    };
+    app.keymap = config.keymap;
+    app.default_col_width = config.col_width;
+    app.theme = config.theme;
+    if let Some(home) = std::env::var_os("HOME") {
+        // Commands and formulas share one file, commands keep their ':' or '/' prompt
+        let file = std::path::Path::new(&home).join(HISTORY_FILE);
+        app.history = History::load(file.clone(), |line| {
+            line.starts_with(':') || line.starts_with('/')
+        });
+        app.formula_history = History::load(file, |line| {
+            !line.starts_with(':') && !line.starts_with('/')
+        });
+    }
+
+    let mut last_save = Instant::now();
+    // Errors are logged once, when they are first shown
+    let mut error_shown = matches!(app.mode, Mode::ErrMsg(_));
     loop {
+        match app.mode {
+            Mode::ErrMsg(msg) if !error_shown => {
+                app.log(msg);
+                error_shown = true;
+            }
+            Mode::ErrMsg(_) => {}
+            _ => error_shown = false,
+        }
         terminal.draw(|f| render::<CrosstermBackend<Stdout>>(f, &mut app))?;
-        if let Some(cmd) = handle_input(&mut app)? {
-            match cmd {
-                AppCommand::Exit => break,
+
+        // With autosave on, waiting for input stops when the next save is due
+        let input_ready = match config.autosave {
+            Some(interval) => event::poll(interval.saturating_sub(last_save.elapsed()))?,
+            None => true,
+        };
+        if input_ready {
+            if let Some(cmd) = handle_input(&mut app)? {
+                match cmd {
+                    AppCommand::Exit => break,
+                }
+            }
+        }
+
+        if let Some(interval) = config.autosave {
+            if last_save.elapsed() >= interval {
+                autosave(&mut app);
+                last_save = Instant::now();
             }
         }
     }
 
+    // The unsaved changes are saved or dropped on a clean exit
+    if !app.file_name.is_empty() {
+        remove_swap(&app.file_name);
+    }
+
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -1,6 +1,32 @@
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
 use std::usize;
 
+use crate::database::format::NumberFormat;
 use crate::database::Database;
+use crate::display::generate_column_label;
+use crate::evaluator::{assign_text, clear_range, recalc};
+use crate::extensions::chart::ChartKind;
+use crate::extensions::clipboard::Clipboard;
+use crate::extensions::complete::{candidates, common_prefix};
+use crate::extensions::cond_format::CondFormat;
+use crate::extensions::fill;
+use crate::extensions::filter::Filter;
+use crate::extensions::history::History;
+use crate::extensions::keymap::Keymap;
+use crate::extensions::search::{find_matches, next_match};
+use crate::extensions::stats::Summary;
+use crate::extensions::theme::Theme;
+use crate::utils::{cell_text, extract_range_data};
+
+/// Width of the columns that were never resized
+pub const DEFAULT_COL_WIDTH: u16 = 14;
+/// Widest a column can be made
+pub const MAX_COL_WIDTH: u16 = 100;
+/// Number of messages kept by the message log
+const MAX_MESSAGES: usize = 100;
+/// Longest time between the two clicks of a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
 
 pub enum AppCommand {
     Exit,
@@ -14,16 +40,51 @@ pub enum Mode<'a> {
     Select(usize, usize),
     ErrMsg(&'a str),
     Graph((usize, usize), (usize, usize)),
+    Stats((usize, usize), (usize, usize)),
+    /// Help screen, scrolled down by the given number of lines
+    Help(u16),
+    /// Message log, scrolled down by the given number of lines
+    Messages(u16),
+    /// Asks whether to recover the swap file found when the open file was loaded
+    Recover,
 }
 
 pub struct App<'a> {
     pub mode: Mode<'a>,
     pub input_buffer: String,
+    pub cursor: usize,
     pub db: Database,
     pub file_name: String,
     pub topleft: (usize, usize),
     pub selected: (usize, usize),
     pub dissz: (usize, usize),
+    pub frozen: (usize, usize),
+    pub clipboard: Option<Clipboard>,
+    pub cmd_selection: Option<((usize, usize), (usize, usize))>,
+    pub filter: Option<Filter>,
+    pub cond_formats: Vec<CondFormat>,
+    pub chart: ChartKind,
+    pub search: Option<String>,
+    pub history: History,
+    pub formula_history: History,
+    pub completion: Option<(usize, Vec<String>, usize)>,
+    /// Screen line of every shown row, set by the render
+    pub screen_rows: Vec<(u16, usize)>,
+    /// Screen x, width and column of every shown column, set by the render
+    pub screen_cols: Vec<(u16, u16, usize)>,
+    /// Width the columns can take in the sheet, set by the render
+    pub sheet_width: u16,
+    pub last_click: Option<((usize, usize), Instant)>,
+    pub keymap: Keymap,
+    /// Width of the columns that were never resized, from the config
+    pub default_col_width: u16,
+    pub theme: Theme,
+    /// Error shown while editing, until the next key press
+    pub error: Option<String>,
+    /// Recent errors and saves, oldest first
+    pub messages: Vec<String>,
+    /// Unsaved changes kept outside of the database, like conditional formatting rules
+    pub dirty: bool,
 }
 
 impl<'a> App<'a> {
@@ -35,24 +96,92 @@ impl<'a> App<'a> {
             selected: (0, 0),
             mode: Mode::Normal,
             input_buffer: String::new(),
+            cursor: 0,
             dissz: (0, 0),
+            frozen: (0, 0),
+            clipboard: None,
+            cmd_selection: None,
+            filter: None,
+            cond_formats: Vec::new(),
+            chart: ChartKind::Bar,
+            search: None,
+            history: History::new(),
+            formula_history: History::new(),
+            completion: None,
+            screen_rows: Vec::new(),
+            screen_cols: Vec::new(),
+            sheet_width: 0,
+            last_click: None,
+            keymap: Keymap::default(),
+            default_col_width: DEFAULT_COL_WIDTH,
+            theme: Theme::default(),
+            error: None,
+            messages: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    /// Checks if the sheet has changes that were not saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty || self.db.is_dirty()
+    }
+
+    /// Clears the unsaved changes, after the sheet was saved or loaded
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+        self.db.mark_saved();
+    }
+
+    /// Checks if row is shown, i.e. not hidden by the active filter
+    pub fn row_visible(&self, row: usize) -> bool {
+        match &self.filter {
+            Some(filter) => filter.matches(&self.db, row),
+            None => true,
+        }
+    }
+
+    /// Sets or removes the row filter
+    /// If the current row gets hidden, the selection moves to the nearest shown row
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+        if !self.row_visible(self.selected.0) {
+            let row = self.selected.0;
+            self.move_down();
+            if self.selected.0 == row {
+                self.move_up();
+            }
         }
     }
 
+    /// Pins the first rows and cols in place, topleft then only scrolls the rest
+    pub fn set_frozen(&mut self, rows: usize, cols: usize) {
+        self.frozen = (rows, cols);
+        self.topleft = (self.topleft.0.max(rows), self.topleft.1.max(cols));
+    }
+
     // Assume num_rows and num_cols are part of your App struct
+    // Rows hidden by the filter are skipped
+    // Moving inside the frozen rows and columns does not scroll
     pub fn move_up(&mut self) {
-        if self.selected.0 > 0 {
-            self.selected.0 -= 1;
-            if self.selected.0 < self.topleft.0 {
-                self.topleft.0 -= 1;
+        if let Some(row) = (0..self.selected.0).rev().find(|&r| self.row_visible(r)) {
+            self.selected.0 = row;
+            if self.selected.0 < self.topleft.0 && self.selected.0 >= self.frozen.0 {
+                self.topleft.0 = self.selected.0;
             }
         }
     }
 
     pub fn move_down(&mut self) {
-        if self.selected.0 + 1 < self.db.num_rows as usize {
-            self.selected.0 += 1;
-            if self.selected.0 > self.topleft.0 + self.dissz.0 {
+        if let Some(row) =
+            (self.selected.0 + 1..self.db.num_rows as usize).find(|&r| self.row_visible(r))
+        {
+            self.selected.0 = row;
+            // Scroll until the shown rows after topleft fit in the display
+            while (self.topleft.0 + 1..=self.selected.0)
+                .filter(|&r| self.row_visible(r))
+                .count()
+                > self.dissz.0
+            {
                 self.topleft.0 += 1;
             }
         }
@@ -61,7 +190,7 @@ impl<'a> App<'a> {
     pub fn move_left(&mut self) {
         if self.selected.1 > 0 {
             self.selected.1 -= 1;
-            if self.selected.1 < self.topleft.1 {
+            if self.selected.1 < self.topleft.1 && self.selected.1 >= self.frozen.1 {
                 self.topleft.1 -= 1;
             }
         }
@@ -70,32 +199,531 @@ impl<'a> App<'a> {
     pub fn move_right(&mut self) {
         if self.selected.1 + 1 < self.db.num_cols as usize {
             self.selected.1 += 1;
-            if self.selected.1 > self.topleft.1 + self.dissz.1 {
-                self.topleft.1 += 1;
+            self.scroll_to_col(self.selected.1);
+        }
+    }
+
+    /// Columns shown from topleft, frozen columns first, while they fit in the sheet width
+    /// Every column takes one more space between columns, and at least one is shown
+    pub fn shown_cols(&self) -> Vec<usize> {
+        let mut free_width = self.sheet_width;
+        (0..self.frozen.1)
+            .chain(self.topleft.1..self.db.num_cols as usize)
+            .enumerate()
+            .take_while(|&(i, c)| {
+                let needed = self.col_width(c) + 1;
+                let fits = needed <= free_width || i == 0;
+                free_width = free_width.saturating_sub(needed);
+                fits
+            })
+            .map(|(_, c)| c)
+            .collect()
+    }
+
+    /// Scrolls the columns right until col is shown, columns can have different widths
+    fn scroll_to_col(&mut self, col: usize) {
+        while self.topleft.1 < col && !self.shown_cols().contains(&col) {
+            self.topleft.1 += 1;
+        }
+    }
+
+    /// Returns the (top-left, bottom-right) corners of the selected range
+    /// Only Select, Graph and Stats modes have a range, other modes return None
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let ((r1, c1), (r2, c2)) = match self.mode {
+            Mode::Select(r, c) => ((r, c), self.selected),
+            Mode::Graph(a, b) | Mode::Stats(a, b) => (a, b),
+            _ => return None,
+        };
+
+        Some(((r1.min(r2), c1.min(c2)), (r1.max(r2), c1.max(c2))))
+    }
+
+    /// Summary statistics of the numbers in the selection, None without a selection or numbers
+    pub fn selection_summary(&self) -> Option<Summary> {
+        let values: Vec<f32> = extract_range_data(self).iter().map(|(_, v)| *v).collect();
+        Summary::from_values(&values)
+    }
+
+    /// Number of error cells in the selection, they are left out of selection_summary
+    pub fn selection_errors(&self) -> usize {
+        match self.selection() {
+            Some(((row_low, col_low), (row_high, col_high))) => (col_low..=col_high)
+                .flat_map(|col| (row_low..=row_high).map(move |row| (1000 * col + row) as u32))
+                .filter(|&cell_idx| self.db.get(cell_idx) == Err(true))
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Columns of the selection, or of the selection the command was run from, or the current column
+    pub fn selected_columns(&self) -> RangeInclusive<usize> {
+        let (start, end) = self
+            .selection()
+            .or(self.cmd_selection)
+            .unwrap_or((self.selected, self.selected));
+        start.1..=end.1
+    }
+
+    /// Display width of col
+    pub fn col_width(&self, col: usize) -> u16 {
+        self.db
+            .get_col_width(col as u32)
+            .unwrap_or(self.default_col_width)
+    }
+
+    /// Sets the width of the selected columns, see selected_columns
+    pub fn set_col_width(&mut self, width: u16) {
+        for col in self.selected_columns() {
+            self.db
+                .set_col_width(col as u32, Some(width.clamp(1, MAX_COL_WIDTH)));
+        }
+    }
+
+    /// Widens (or narrows if delta is negative) the selected columns by delta
+    pub fn resize_columns(&mut self, delta: i32) {
+        for col in self.selected_columns() {
+            let width = (self.col_width(col) as i32 + delta).clamp(1, MAX_COL_WIDTH as i32);
+            self.db.set_col_width(col as u32, Some(width as u16));
+        }
+    }
+
+    /// Fits the width of the selected columns to their widest displayed value and their label
+    pub fn autofit(&mut self) {
+        for col in self.selected_columns() {
+            let widest = (0..self.db.num_rows as usize)
+                .filter(|&row| self.row_visible(row))
+                .map(|row| self.get_value(row, col).chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(generate_column_label(col as u32).len());
+            let width = (widest as u16).clamp(1, MAX_COL_WIDTH);
+            self.db.set_col_width(col as u32, Some(width));
+        }
+    }
+
+    /// Copies the selection, or the current cell outside Select mode, into the clipboard
+    pub fn yank(&mut self) {
+        let (start, end) = self.selection().unwrap_or((self.selected, self.selected));
+        self.clipboard = Some(Clipboard::copy(&self.db, start, end));
+    }
+
+    /// Same as yank, but also clears the copied cells
+    pub fn cut(&mut self) {
+        let (start, end) = self.selection().unwrap_or((self.selected, self.selected));
+        self.clipboard = Some(Clipboard::cut(&mut self.db, start, end));
+    }
+
+    /// Pastes the clipboard with its top-left corner at the current cell
+    /// Returns the evaluator error code
+    pub fn paste(&mut self) -> i32 {
+        match &self.clipboard {
+            Some(clipboard) => clipboard.paste(&mut self.db, self.selected),
+            None => 0,
+        }
+    }
+
+    /// Empties the selection, or the current cell outside Select mode
+    pub fn clear(&mut self) {
+        let (start, end) = self.selection().unwrap_or((self.selected, self.selected));
+        clear_range(
+            &mut self.db,
+            (1000 * start.1 + start.0) as u32,
+            (1000 * end.1 + end.0) as u32,
+        );
+    }
+
+    /// Fills the selection downwards from its first row
+    /// Returns the evaluator error code
+    pub fn fill_down(&mut self) -> i32 {
+        let (start, end) = self.selection().unwrap_or((self.selected, self.selected));
+        fill::fill_down(&mut self.db, start, end)
+    }
+
+    /// Fills the selection rightwards from its first column
+    /// Returns the evaluator error code
+    pub fn fill_right(&mut self) -> i32 {
+        let (start, end) = self.selection().unwrap_or((self.selected, self.selected));
+        fill::fill_right(&mut self.db, start, end)
+    }
+
+    /// Moves the selection to (row, col), scrolling as little as possible so that it is displayed
+    pub fn jump_to(&mut self, row: usize, col: usize) {
+        self.selected = (row, col);
+
+        if row < self.topleft.0 {
+            self.topleft.0 = row.max(self.frozen.0);
+        }
+        while (self.topleft.0 + 1..=row)
+            .filter(|&r| self.row_visible(r))
+            .count()
+            > self.dissz.0
+        {
+            self.topleft.0 += 1;
+        }
+
+        if col < self.topleft.1 {
+            self.topleft.1 = col.max(self.frozen.1);
+        } else {
+            self.scroll_to_col(col);
+        }
+    }
+
+    /// Jumps to the first shown row
+    pub fn jump_first_row(&mut self) {
+        if let Some(row) = (0..self.db.num_rows as usize).find(|&r| self.row_visible(r)) {
+            self.jump_to(row, self.selected.1);
+        }
+    }
+
+    /// Jumps to the last shown row
+    pub fn jump_last_row(&mut self) {
+        if let Some(row) = (0..self.db.num_rows as usize)
+            .rev()
+            .find(|&r| self.row_visible(r))
+        {
+            self.jump_to(row, self.selected.1);
+        }
+    }
+
+    /// Moves half a page down, like move_down repeated
+    pub fn page_down(&mut self) {
+        for _ in 0..(self.dissz.0 + 1).div_ceil(2) {
+            self.move_down();
+        }
+    }
+
+    /// Moves half a page up, like move_up repeated
+    pub fn page_up(&mut self) {
+        for _ in 0..(self.dissz.0 + 1).div_ceil(2) {
+            self.move_up();
+        }
+    }
+
+    /// Jumps in the direction (drow, dcol), one of them being 0 and the other 1 or -1
+    /// From a non-empty cell followed by another, jumps to the last non-empty cell of the block
+    /// Otherwise jumps to the next non-empty cell, or to the edge of the sheet if there is none
+    /// Rows hidden by the filter are skipped
+    pub fn jump_edge(&mut self, drow: i32, dcol: i32) {
+        let mut line: Vec<(usize, usize)> = Vec::new();
+        let (row, col) = self.selected;
+        if drow > 0 {
+            line.extend((row + 1..self.db.num_rows as usize).map(|r| (r, col)));
+        } else if drow < 0 {
+            line.extend((0..row).rev().map(|r| (r, col)));
+        } else if dcol > 0 {
+            line.extend((col + 1..self.db.num_cols as usize).map(|c| (row, c)));
+        } else if dcol < 0 {
+            line.extend((0..col).rev().map(|c| (row, c)));
+        }
+        line.retain(|&(r, _)| self.row_visible(r));
+
+        let filled = |(r, c): (usize, usize)| {
+            !matches!(
+                self.db.get((1000 * c + r) as u32),
+                Ok(crate::database::cell::CellData::Empty)
+            )
+        };
+
+        let target = if filled(self.selected) && line.first().is_some_and(|&cell| filled(cell)) {
+            line.iter().take_while(|&&cell| filled(cell)).last()
+        } else {
+            line.iter().find(|&&cell| filled(cell)).or(line.last())
+        };
+
+        if let Some(&(r, c)) = target {
+            self.jump_to(r, c);
+        }
+    }
+
+    /// Jumps to the next cell (or previous if forward is false) matching the last search pattern
+    /// Rows hidden by the filter are skipped
+    /// Returns false if there is no match
+    pub fn search_next(&mut self, forward: bool) -> bool {
+        let pattern = match &self.search {
+            Some(pattern) => pattern,
+            None => return false,
+        };
+
+        let matches: Vec<(usize, usize)> = find_matches(&self.db, pattern)
+            .into_iter()
+            .filter(|&(r, _)| self.row_visible(r))
+            .collect();
+
+        match next_match(&matches, self.selected, forward) {
+            Some((row, col)) => {
+                self.jump_to(row, col);
+                true
             }
+            None => false,
+        }
+    }
+
+    /// Edits cell in Insert mode, starting from its formula or its full-precision value
+    /// An error constant has no text giving it back, so its line starts empty
+    pub fn edit(&mut self, cell: (usize, usize)) {
+        let text = cell_text(&self.db, (1000 * cell.1 + cell.0) as u32);
+        self.set_input(if text == "#ERR" { "" } else { &text });
+        self.mode = Mode::Insert;
+    }
+
+    /// Writes the input buffer into the current cell, a number or a formula starting with '='
+    /// The cell and the cells depending on it are recalculated
+    /// Returns the evaluator error code, 0 if the cell was written
+    pub fn commit_input(&mut self) -> i32 {
+        let cell_idx = (1000 * self.selected.1 + self.selected.0) as u32;
+        let ec = assign_text(&mut self.db, cell_idx, &self.input_buffer);
+        if ec == 0 {
+            recalc(&mut self.db, &[cell_idx]);
+        }
+        ec
+    }
+
+    /// Replaces the input buffer by text, with the cursor at its end
+    /// History browsing starts again from the newest entry
+    pub fn set_input(&mut self, text: &str) {
+        self.input_buffer = text.to_string();
+        self.cursor = self.input_buffer.len();
+        self.history.reset();
+        self.formula_history.reset();
+    }
+
+    /// Shows the previous history entry, formulas in Insert mode and commands otherwise
+    pub fn history_prev(&mut self) {
+        let history = if self.mode == Mode::Insert {
+            &mut self.formula_history
+        } else {
+            &mut self.history
+        };
+        if let Some(entry) = history.prev(&self.input_buffer) {
+            self.input_buffer = entry.to_string();
+            self.cursor = self.input_buffer.len();
         }
     }
 
+    /// Shows the next history entry, or the line that was being typed
+    pub fn history_next(&mut self) {
+        let history = if self.mode == Mode::Insert {
+            &mut self.formula_history
+        } else {
+            &mut self.history
+        };
+        if let Some(entry) = history.next() {
+            self.input_buffer = entry.to_string();
+            self.cursor = self.input_buffer.len();
+        }
+    }
+
+    /// Completes the word before the cursor, see complete::candidates
+    /// A single candidate replaces the word, otherwise their common prefix does
+    /// When there is nothing more in common, repeated calls cycle through the candidates
+    pub fn complete(&mut self) {
+        let pos = self.cursor_pos();
+
+        if let Some((start, cands, idx)) = &mut self.completion {
+            *idx = (*idx + 1) % cands.len();
+            self.input_buffer.replace_range(*start..pos, &cands[*idx]);
+            self.cursor = *start + cands[*idx].len();
+            return;
+        }
+
+        let (start, cands) = candidates(
+            &self.input_buffer[..pos],
+            self.mode != Mode::Insert && self.input_buffer.starts_with(':'),
+        );
+        let replacement = match cands.len() {
+            0 => return,
+            1 => cands[0].clone(),
+            _ if common_prefix(&cands).len() > pos - start => common_prefix(&cands).to_string(),
+            _ => {
+                let first = cands[0].clone();
+                self.completion = Some((start, cands, 0));
+                first
+            }
+        };
+
+        self.input_buffer.replace_range(start..pos, &replacement);
+        self.cursor = start + replacement.len();
+    }
+
+    /// Byte position of the cursor in the input buffer, kept at the end if the buffer shrank
+    pub fn cursor_pos(&self) -> usize {
+        self.cursor.min(self.input_buffer.len())
+    }
+
+    /// Inserts c at the cursor
+    pub fn insert_char(&mut self, c: char) {
+        let pos = self.cursor_pos();
+        self.input_buffer.insert(pos, c);
+        self.cursor = pos + c.len_utf8();
+    }
+
+    /// Deletes the char before the cursor
+    pub fn delete_back(&mut self) {
+        let pos = self.cursor_pos();
+        if let Some(c) = self.input_buffer[..pos].chars().next_back() {
+            self.cursor = pos - c.len_utf8();
+            self.input_buffer.remove(self.cursor);
+        }
+    }
+
+    /// Deletes the char under the cursor
+    pub fn delete_forward(&mut self) {
+        let pos = self.cursor_pos();
+        if pos < self.input_buffer.len() {
+            self.input_buffer.remove(pos);
+        }
+        self.cursor = pos;
+    }
+
+    pub fn cursor_left(&mut self) {
+        let pos = self.cursor_pos();
+        self.cursor = pos
+            - self.input_buffer[..pos]
+                .chars()
+                .next_back()
+                .map_or(0, char::len_utf8);
+    }
+
+    pub fn cursor_right(&mut self) {
+        let pos = self.cursor_pos();
+        self.cursor = pos
+            + self.input_buffer[pos..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+    }
+
+    pub fn cursor_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn cursor_end(&mut self) {
+        self.cursor = self.input_buffer.len();
+    }
+
+    /// Moves the cursor to the start of the previous word, words being cell names, functions and numbers
+    pub fn word_left(&mut self) {
+        let before = &self.input_buffer[..self.cursor_pos()];
+        let end = before.trim_end_matches(|c: char| !is_word_char(c)).len();
+        self.cursor = before[..end].trim_end_matches(is_word_char).len();
+    }
+
+    /// Moves the cursor past the end of the next word
+    pub fn word_right(&mut self) {
+        let pos = self.cursor_pos();
+        let after = self.input_buffer[pos..].trim_start_matches(|c: char| !is_word_char(c));
+        let after = after.trim_start_matches(is_word_char);
+        self.cursor = self.input_buffer.len() - after.len();
+    }
+
+    /// Adds msg to the message log, dropping the oldest messages past MAX_MESSAGES
+    pub fn log(&mut self, msg: &str) {
+        self.messages.push(msg.to_string());
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    /// Shows an error without leaving the line being edited, and logs it
+    pub fn show_error(&mut self, msg: &str) {
+        self.error = Some(msg.to_string());
+        self.log(msg);
+    }
+
     pub fn set_dissz(&mut self, sz: (usize, usize)) {
         self.dissz = sz;
     }
 
+    /// Cell shown at the screen position (x, y), None outside the cells
+    pub fn cell_at(&self, x: u16, y: u16) -> Option<(usize, usize)> {
+        let row = self
+            .screen_rows
+            .iter()
+            .find(|&&(line, _)| line == y)
+            .map(|&(_, row)| row)?;
+        let col = self
+            .screen_cols
+            .iter()
+            .find(|&&(start, width, _)| (start..start + width).contains(&x))
+            .map(|&(_, _, col)| col)?;
+        Some((row, col))
+    }
+
+    /// Selects the clicked cell, clicked at time now
+    /// Returns true if it is the second click on the cell of a double click
+    pub fn click(&mut self, cell: (usize, usize), now: Instant) -> bool {
+        let double = matches!(self.last_click,
+            Some((last, time)) if last == cell && now.duration_since(time) <= DOUBLE_CLICK);
+        self.last_click = if double { None } else { Some((cell, now)) };
+        self.selected = cell;
+        double
+    }
+
+    /// Scrolls the rows by delta shown rows, up if delta is negative, without moving past the last row
+    /// The selection moves along when it would leave the display, frozen rows never scroll
+    pub fn scroll(&mut self, delta: i32) {
+        let num_rows = self.db.num_rows as usize;
+        for _ in 0..delta.unsigned_abs() {
+            let next = if delta > 0 {
+                let rows_left = (self.topleft.0..num_rows)
+                    .filter(|&r| self.row_visible(r))
+                    .count();
+                if rows_left <= self.dissz.0 + 1 {
+                    break;
+                }
+                (self.topleft.0 + 1..num_rows).find(|&r| self.row_visible(r))
+            } else {
+                (self.frozen.0..self.topleft.0)
+                    .rev()
+                    .find(|&r| self.row_visible(r))
+            };
+            match next {
+                Some(row) => self.topleft.0 = row,
+                None => break,
+            }
+        }
+
+        if self.selected.0 >= self.frozen.0 {
+            let shown: Vec<usize> = (self.topleft.0..num_rows)
+                .filter(|&r| self.row_visible(r))
+                .take(self.dissz.0 + 1)
+                .collect();
+            if let (Some(&first), Some(&last)) = (shown.first(), shown.last()) {
+                self.selected.0 = self.selected.0.clamp(first, last);
+            }
+        }
+    }
+
+    /// Displayed value of a cell, in its number format if it has one
     pub fn get_value(&self, row: usize, col: usize) -> String {
         let id = (1000 * col + row) as u32;
-        match self.db.get_cell(id) {
-            Ok(&c) => match c.get_data() {
-                Ok(data) => data.to_string(),
-                Err(()) => "ERR".to_string(),
-            },
-            Err(true) => "0".to_string(),
+        match self.db.get_formatted(id) {
+            Ok(text) => text,
+            Err(true) => "ERR".to_string(),
             Err(false) => panic!("cell out of range"),
         }
     }
+
+    /// Sets the number format of the selection the command was run from, or of the current cell
+    pub fn set_format(&mut self, format: Option<NumberFormat>) {
+        let (start, end) = self.cmd_selection.unwrap_or((self.selected, self.selected));
+        for col in start.1..=end.1 {
+            for row in start.0..=end.0 {
+                self.db.set_format((1000 * col + row) as u32, format);
+            }
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.'
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::cell::CellData;
 
     // Helper function to create a default App with a mock database
     fn create_test_app(rows: u16, cols: u16) -> App<'static> {
@@ -103,6 +731,12 @@ mod tests {
         App::new(db)
     }
 
+    // Sets the display like the render does for columns of the default width
+    fn set_display(app: &mut App, sz: (usize, usize)) {
+        app.set_dissz(sz);
+        app.sheet_width = (app.frozen.1 + sz.1 + 1) as u16 * (DEFAULT_COL_WIDTH + 1);
+    }
+
     #[test]
     fn test_app_new() {
         let app = create_test_app(10, 5);
@@ -170,6 +804,314 @@ mod tests {
         assert_eq!(app.topleft, (0, 0));
     }
 
+    #[test]
+    fn test_move_with_filter() {
+        let mut app = create_test_app(10, 5);
+        for row in 0..10 {
+            let _ = app.db.set_int(row, row as i32);
+        }
+        app.set_dissz((2, 5));
+        app.selected = (1, 0);
+
+        // Only even rows are shown, the current row moves to the next shown row
+        app.set_filter(Some(Filter {
+            col: 0,
+            op: crate::extensions::filter::FilterOp::Ne,
+            value: 1.0,
+            rows: (0, 9),
+        }));
+        assert_eq!(app.selected, (2, 0));
+
+        app.filter = Some(Filter::parse("A < 7", (3, 9), (0, 4)).unwrap());
+        app.move_down();
+        assert_eq!(app.selected, (3, 0));
+        assert_eq!(app.topleft, (1, 0));
+        app.move_down();
+        app.move_down();
+        app.move_down();
+        assert_eq!(app.selected, (6, 0));
+        app.move_down();
+        assert_eq!(app.selected, (6, 0));
+
+        app.filter = Some(Filter::parse("A > 7", (3, 9), (0, 4)).unwrap());
+        app.selected = (8, 0);
+        app.move_up();
+        assert_eq!(app.selected, (2, 0));
+        assert_eq!(app.topleft, (2, 0));
+    }
+
+    #[test]
+    fn test_search_next() {
+        let mut app = create_test_app(10, 5);
+        let _ = app.db.set_int(3, 42);
+        let _ = app.db.set_int(2001, 142);
+        set_display(&mut app, (2, 2));
+
+        assert!(!app.search_next(true));
+
+        app.search = Some("42".to_string());
+        assert!(app.search_next(true));
+        assert_eq!(app.selected, (1, 2));
+        assert!(app.search_next(true));
+        assert_eq!(app.selected, (3, 0));
+        assert_eq!(app.topleft, (1, 0));
+        assert!(app.search_next(true));
+        assert_eq!(app.selected, (1, 2));
+        assert_eq!(app.topleft, (1, 0));
+        assert!(app.search_next(false));
+        assert_eq!(app.selected, (3, 0));
+
+        app.search = Some("7".to_string());
+        assert!(!app.search_next(true));
+        assert_eq!(app.selected, (3, 0));
+    }
+
+    #[test]
+    fn test_line_editing() {
+        let mut app = create_test_app(10, 5);
+        app.set_input("=SUM(A1:B2)");
+        assert_eq!(app.cursor, 11);
+
+        app.cursor_left();
+        app.word_left();
+        assert_eq!(app.cursor, 8);
+        app.word_left();
+        assert_eq!(app.cursor, 5);
+        app.word_left();
+        assert_eq!(app.cursor, 1);
+        app.word_left();
+        assert_eq!(app.cursor, 0);
+
+        app.word_right();
+        assert_eq!(app.cursor, 4);
+        app.word_right();
+        assert_eq!(app.cursor, 7);
+
+        app.delete_back();
+        app.insert_char('3');
+        assert_eq!(app.input_buffer, "=SUM(A3:B2)");
+        app.delete_forward();
+        app.insert_char(',');
+        app.cursor_left();
+        app.delete_forward();
+        app.insert_char(':');
+        assert_eq!(app.input_buffer, "=SUM(A3:B2)");
+
+        app.cursor_home();
+        app.delete_back();
+        app.delete_forward();
+        assert_eq!(app.input_buffer, "SUM(A3:B2)");
+        app.cursor_end();
+        app.cursor_right();
+        assert_eq!(app.cursor, 10);
+
+        // A cleared buffer keeps the cursor at its end
+        app.input_buffer.clear();
+        app.insert_char('5');
+        assert_eq!((app.input_buffer.as_str(), app.cursor), ("5", 1));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut app = create_test_app(10, 5);
+        app.history.push(":w a.csv");
+        app.formula_history.push("=A1+1");
+
+        app.mode = Mode::NormalCommand;
+        app.set_input(":o");
+        app.history_prev();
+        assert_eq!((app.input_buffer.as_str(), app.cursor), (":w a.csv", 8));
+        app.history_prev();
+        assert_eq!(app.input_buffer, ":w a.csv");
+        app.history_next();
+        assert_eq!(app.input_buffer, ":o");
+
+        app.mode = Mode::Insert;
+        app.set_input("");
+        app.history_prev();
+        assert_eq!(app.input_buffer, "=A1+1");
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut app = create_test_app(10, 5);
+        app.mode = Mode::NormalCommand;
+        app.set_input(":go");
+        app.complete();
+        assert_eq!((app.input_buffer.as_str(), app.cursor), (":goto", 5));
+
+        // Cycling through w and wq
+        app.set_input(":w");
+        app.complete();
+        assert_eq!(app.input_buffer, ":w");
+        app.complete();
+        assert_eq!(app.input_buffer, ":wq");
+        app.complete();
+        assert_eq!(app.input_buffer, ":w");
+
+        app.completion = None;
+        app.mode = Mode::Insert;
+        app.set_input("=ST(A1:A2)");
+        app.cursor = 3;
+        app.complete();
+        assert_eq!(app.input_buffer, "=STDEV(A1:A2)");
+        assert_eq!(app.cursor, 6);
+    }
+
+    #[test]
+    fn test_col_widths() {
+        let mut app = create_test_app(10, 5);
+        assert_eq!(app.col_width(0), DEFAULT_COL_WIDTH);
+
+        app.set_col_width(20);
+        assert_eq!(app.col_width(0), 20);
+        app.resize_columns(-25);
+        assert_eq!(app.col_width(0), 1);
+
+        app.selected = (0, 3);
+        app.mode = Mode::Select(0, 1);
+        app.resize_columns(2);
+        assert_eq!(app.col_width(0), 1);
+        assert_eq!(
+            (app.col_width(1), app.col_width(3)),
+            (DEFAULT_COL_WIDTH + 2, DEFAULT_COL_WIDTH + 2)
+        );
+
+        let _ = app.db.set_int(1004, 123456);
+        let _ = app.db.set_float(2002, -1.5);
+        app.autofit();
+        assert_eq!(
+            (app.col_width(1), app.col_width(2), app.col_width(3)),
+            (6, 5, 1)
+        );
+    }
+
+    #[test]
+    fn test_frozen() {
+        let mut app = create_test_app(20, 10);
+        app.topleft = (0, 0);
+        app.set_frozen(1, 2);
+        set_display(&mut app, (3, 2));
+        assert_eq!(app.topleft, (1, 2));
+
+        for _ in 0..5 {
+            app.move_down();
+        }
+        assert_eq!(app.selected.0, 5);
+        assert_eq!(app.topleft.0, 2);
+
+        // Moving up into the header row does not scroll past the frozen rows
+        for _ in 0..5 {
+            app.move_up();
+        }
+        assert_eq!(app.selected.0, 0);
+        assert_eq!(app.topleft.0, 1);
+
+        for _ in 0..6 {
+            app.move_right();
+        }
+        assert_eq!(app.selected.1, 6);
+        assert_eq!(app.topleft.1, 4);
+        for _ in 0..6 {
+            app.move_left();
+        }
+        assert_eq!(app.topleft.1, 2);
+
+        app.jump_to(15, 9);
+        assert_eq!(app.topleft, (12, 7));
+        // Jumping into the frozen region scrolls back to the start
+        app.jump_to(0, 0);
+        assert_eq!(app.topleft, (1, 2));
+    }
+
+    #[test]
+    fn test_scroll_mixed_widths() {
+        let mut app = create_test_app(10, 10);
+        for col in 0..5 {
+            app.db.set_col_width(col, Some(5));
+        }
+        app.db.set_col_width(5, Some(30));
+        app.db.set_col_width(6, Some(20));
+        app.sheet_width = 60;
+
+        // Six narrow columns take 36, so the wide column needs more than one column scrolled
+        for _ in 0..5 {
+            app.move_right();
+        }
+        assert_eq!(app.selected.1, 5);
+        assert!(app.shown_cols().contains(&5));
+        assert_eq!(app.topleft.1, 1);
+        app.move_right();
+        assert_eq!(app.shown_cols(), vec![4, 5, 6]);
+
+        // The frozen column keeps its room when jumping
+        app.jump_to(0, 0);
+        app.set_frozen(0, 1);
+        app.jump_to(0, 6);
+        assert_eq!(app.shown_cols(), vec![0, 5, 6]);
+        app.jump_to(0, 3);
+        assert_eq!(app.topleft.1, 3);
+        assert!(app.shown_cols().contains(&3));
+    }
+
+    #[test]
+    fn test_jumps() {
+        let mut app = create_test_app(10, 5);
+        set_display(&mut app, (3, 2));
+
+        app.jump_last_row();
+        assert_eq!(app.selected, (9, 0));
+        assert_eq!(app.topleft, (6, 0));
+        app.jump_first_row();
+        assert_eq!(app.selected, (0, 0));
+        assert_eq!(app.topleft, (0, 0));
+
+        app.jump_to(0, 4);
+        assert_eq!(app.topleft, (0, 2));
+        app.jump_to(0, 0);
+
+        app.page_down();
+        assert_eq!(app.selected, (2, 0));
+        app.page_down();
+        app.page_down();
+        assert_eq!(app.selected, (6, 0));
+        assert_eq!(app.topleft, (3, 0));
+        app.page_up();
+        assert_eq!(app.selected, (4, 0));
+        assert_eq!(app.topleft, (3, 0));
+    }
+
+    #[test]
+    fn test_jump_edge() {
+        let mut app = create_test_app(10, 5);
+        app.set_dissz((3, 2));
+        for row in [2, 3, 4, 7] {
+            let _ = app.db.set_int(row, 1);
+        }
+
+        // Empty to the next non-empty cell
+        app.jump_edge(1, 0);
+        assert_eq!(app.selected, (2, 0));
+        // Through the block
+        app.jump_edge(1, 0);
+        assert_eq!(app.selected, (4, 0));
+        // From the end of a block to the next one
+        app.jump_edge(1, 0);
+        assert_eq!(app.selected, (7, 0));
+        // To the edge when nothing is left
+        app.jump_edge(1, 0);
+        assert_eq!(app.selected, (9, 0));
+        app.jump_edge(-1, 0);
+        assert_eq!(app.selected, (7, 0));
+
+        app.jump_edge(0, 1);
+        assert_eq!(app.selected, (7, 4));
+        app.jump_edge(0, -1);
+        assert_eq!(app.selected, (7, 0));
+        app.jump_edge(0, -1);
+        assert_eq!(app.selected, (7, 0));
+    }
+
     #[test]
     fn test_move_left() {
         let mut app = create_test_app(10, 5);
@@ -206,14 +1148,14 @@ mod tests {
         let mut app = create_test_app(10, 5);
         app.selected = (0, 0); // Start at the left
         app.topleft = (0, 0);
-        app.set_dissz((5, 3)); // Display 3 columns
+        set_display(&mut app, (5, 3)); // Display 3 columns
 
         app.move_right();
         assert_eq!(app.selected, (0, 1));
         assert_eq!(app.topleft, (0, 0)); // topleft shouldn't change
 
         app.selected = (0, 3); // Move close to the right
-        app.set_dissz((5, 3)); // Display 3 columns
+        set_display(&mut app, (5, 3)); // Display 3 columns
         app.topleft = (0, 2); // Adjust topleft for this scenario
 
         app.move_right();
@@ -227,7 +1169,7 @@ mod tests {
 
         app.selected = (0, 2);
         app.topleft = (0, 0);
-        app.set_dissz((5, 3)); // Display 3 columns
+        set_display(&mut app, (5, 3)); // Display 3 columns
 
         app.move_right();
         assert_eq!(app.selected, (0, 3));
@@ -246,6 +1188,203 @@ mod tests {
         assert_eq!(app.dissz, (5, 5));
     }
 
+    #[test]
+    fn test_selection() {
+        let mut app = create_test_app(10, 5);
+        assert_eq!(app.selection(), None);
+
+        app.mode = Mode::Select(4, 1);
+        app.selected = (2, 3);
+        assert_eq!(app.selection(), Some(((2, 1), (4, 3))));
+
+        app.mode = Mode::Graph((4, 1), (2, 3));
+        assert_eq!(app.selection(), Some(((2, 1), (4, 3))));
+
+        app.mode = Mode::Stats((4, 1), (2, 3));
+        assert_eq!(app.selection(), Some(((2, 1), (4, 3))));
+    }
+
+    #[test]
+    fn test_edit_and_commit() {
+        let mut app = create_test_app(10, 5);
+        let _ = app.db.set_float(0, 1.505);
+        app.edit((0, 0));
+        assert_eq!(app.mode, Mode::Insert);
+        assert_eq!(app.input_buffer, "1.505");
+        // An unchanged float line is written back as it was
+        assert_eq!(app.commit_input(), 0);
+        assert!(app.db.get(0) == Ok(&CellData::FloatData(1.505)));
+
+        app.selected = (1, 0);
+        app.set_input("=A1*2");
+        assert_eq!(app.commit_input(), 0);
+        assert!(app.db.get(1) == Ok(&CellData::FloatData(3.01)));
+        app.edit((1, 0));
+        assert_eq!(app.input_buffer, "=A1*2");
+
+        app.set_input("=A1+");
+        assert_eq!(app.commit_input(), 1);
+
+        // Error constants start from an empty line
+        let _ = app.db.set_int(2, 0);
+        let _ = app.db.set_error(2, true);
+        app.edit((2, 0));
+        assert_eq!(app.input_buffer, "");
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut app = create_test_app(10, 5);
+        app.mode = Mode::Insert;
+        app.set_input("=SUM(A1:A3");
+        app.show_error("parse error");
+        assert_eq!(app.error.as_deref(), Some("parse error"));
+        assert_eq!(app.input_buffer, "=SUM(A1:A3");
+        assert_eq!(app.mode, Mode::Insert);
+
+        for i in 0..MAX_MESSAGES {
+            app.log(&format!("Saved {}.csv", i));
+        }
+        assert_eq!(app.messages.len(), MAX_MESSAGES);
+        assert_eq!(app.messages[0], "Saved 0.csv");
+        assert_eq!(app.messages[MAX_MESSAGES - 1], "Saved 99.csv");
+    }
+
+    #[test]
+    fn test_dirty() {
+        let mut app = create_test_app(10, 5);
+        assert!(!app.is_dirty());
+
+        app.db.set_int(1, 5).unwrap();
+        assert!(app.is_dirty());
+        app.mark_saved();
+        assert!(!app.is_dirty());
+
+        app.db.set_col_width(0, Some(20));
+        assert!(app.is_dirty());
+        app.mark_saved();
+
+        // Reading the sheet does not change it
+        let _ = app.db.get(1);
+        let _ = app.db.get_formatted(1);
+        assert!(!app.is_dirty());
+
+        app.dirty = true;
+        assert!(app.is_dirty());
+        app.mark_saved();
+        assert!(!app.is_dirty());
+    }
+
+    #[test]
+    fn test_cell_at() {
+        let mut app = create_test_app(10, 5);
+        app.screen_rows = vec![(3, 0), (4, 4)];
+        app.screen_cols = vec![(7, 14, 0), (22, 5, 3)];
+
+        assert_eq!(app.cell_at(7, 3), Some((0, 0)));
+        assert_eq!(app.cell_at(26, 4), Some((4, 3)));
+        // Row labels, the space between columns and the header are not cells
+        assert_eq!(app.cell_at(2, 3), None);
+        assert_eq!(app.cell_at(21, 3), None);
+        assert_eq!(app.cell_at(8, 2), None);
+    }
+
+    #[test]
+    fn test_click() {
+        let mut app = create_test_app(10, 5);
+        let now = Instant::now();
+
+        assert!(!app.click((1, 2), now));
+        assert_eq!(app.selected, (1, 2));
+        assert!(app.click((1, 2), now + Duration::from_millis(100)));
+        // A third click starts a new double click
+        assert!(!app.click((1, 2), now + Duration::from_millis(200)));
+
+        assert!(!app.click((2, 2), now + Duration::from_millis(300)));
+        assert!(!app.click((2, 2), now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut app = create_test_app(20, 5);
+        app.dissz = (4, 2);
+        app.selected = (1, 0);
+
+        app.scroll(3);
+        assert_eq!((app.topleft.0, app.selected.0), (3, 3));
+        app.scroll(100);
+        assert_eq!((app.topleft.0, app.selected.0), (15, 15));
+        app.scroll(-2);
+        assert_eq!((app.topleft.0, app.selected.0), (13, 15));
+        app.scroll(-100);
+        assert_eq!((app.topleft.0, app.selected.0), (0, 4));
+
+        // Frozen rows stay, and so does a selection in them
+        app.set_frozen(2, 0);
+        app.selected = (1, 0);
+        app.scroll(5);
+        assert_eq!((app.topleft.0, app.selected.0), (7, 1));
+        app.scroll(-10);
+        assert_eq!(app.topleft.0, 2);
+    }
+
+    #[test]
+    fn test_selection_summary() {
+        let mut app = create_test_app(10, 5);
+        let _ = app.db.set_int(0, 4);
+        let _ = app.db.set_float(1, 2.5);
+        let _ = app.db.set_int(1001, 10);
+        assert_eq!(app.selection_summary(), None);
+
+        app.mode = Mode::Select(0, 0);
+        app.selected = (2, 0);
+        let summary = app.selection_summary().unwrap();
+        assert_eq!((summary.count, summary.sum), (2, 6.5));
+
+        // The summary follows the selection
+        app.selected = (1, 1);
+        let summary = app.selection_summary().unwrap();
+        assert_eq!((summary.count, summary.sum, summary.mean), (3, 16.5, 5.5));
+
+        app.selected = (5, 4);
+        app.mode = Mode::Select(5, 4);
+        assert_eq!(app.selection_summary(), None);
+        assert_eq!(app.selection_errors(), 0);
+
+        // Error cells are counted apart, not as zeros
+        let _ = app.db.set_int(2, 0);
+        let _ = app.db.set_error(2, true);
+        app.mode = Mode::Select(0, 0);
+        app.selected = (2, 0);
+        let summary = app.selection_summary().unwrap();
+        assert_eq!((summary.count, summary.sum, summary.min), (2, 6.5, 2.5));
+        assert_eq!(app.selection_errors(), 1);
+    }
+
+    #[test]
+    fn test_yank_paste() {
+        let mut app = create_test_app(10, 5);
+        let _ = app.db.set_int(0, 7);
+        let _ = app.db.set_int(1000, 8);
+
+        app.mode = Mode::Select(0, 0);
+        app.selected = (0, 1);
+        app.yank();
+
+        app.mode = Mode::Normal;
+        app.selected = (3, 2);
+        assert_eq!(app.paste(), 0);
+        assert_eq!(app.get_value(3, 2), "7");
+        assert_eq!(app.get_value(3, 3), "8");
+
+        app.selected = (0, 0);
+        app.cut();
+        assert_eq!(app.get_value(0, 0), "");
+        app.selected = (5, 0);
+        assert_eq!(app.paste(), 0);
+        assert_eq!(app.get_value(5, 0), "7");
+    }
+
     #[test]
     #[should_panic(expected = "cell out of range")]
     fn test_get_value_out_of_range() {
@@ -259,24 +1398,46 @@ mod tests {
         let app = create_test_app(10, 5);
         // MockDatabase is set up to return Err(true) for cell ID 1001 (col 1, row 1)
         let value = app.get_value(1, 1);
-        assert_eq!(value, "0");
+        assert_eq!(value, "");
     }
 
     #[test]
     fn test_get_value_data_error() {
         let app = create_test_app(10, 5);
         let value = app.get_value(2, 2);
-        assert_eq!(value, "0");
+        assert_eq!(value, "");
     }
 
     #[test]
     fn test_get_value_success() {
         let mut app = create_test_app(10, 5);
         let value = app.get_value(0, 0);
-        assert_eq!(value, "0");
+        assert_eq!(value, "");
 
         let _ = app.db.set_int(3005, 3005);
         let value = app.get_value(5, 3);
         assert_eq!(value, "3005"); // The mock cell returns the ID as a string
     }
+
+    #[test]
+    fn test_get_value_formatted() {
+        let mut app = create_test_app(10, 5);
+        let _ = app.db.set_float(1000, 1234.5);
+        let _ = app.db.set_float(1001, 0.25);
+        assert_eq!(app.get_value(0, 1), "1234.50");
+
+        app.selected = (0, 1);
+        app.set_format(NumberFormat::parse("#,##0.000"));
+        assert_eq!(app.get_value(0, 1), "1,234.500");
+        assert_eq!(app.get_value(1, 1), "0.25");
+
+        app.cmd_selection = Some(((0, 1), (2, 1)));
+        app.set_format(NumberFormat::parse("0%"));
+        assert_eq!(app.get_value(0, 1), "123450%");
+        assert_eq!(app.get_value(1, 1), "25%");
+        assert_eq!(app.get_value(2, 1), "");
+
+        app.set_format(None);
+        assert_eq!(app.get_value(1, 1), "0.25");
+    }
 }
@@ -0,0 +1,207 @@
+use crate::database::cell::CellData;
+use crate::database::Database;
+use crate::display::generate_column_label;
+
+/// Kind of chart drawn in Graph mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Bar,
+    Line,
+    Scatter,
+    Sparkline,
+}
+
+impl ChartKind {
+    /// Kind shown after this one when toggling
+    pub fn next(self) -> ChartKind {
+        match self {
+            ChartKind::Bar => ChartKind::Line,
+            ChartKind::Line => ChartKind::Scatter,
+            ChartKind::Scatter => ChartKind::Sparkline,
+            ChartKind::Sparkline => ChartKind::Bar,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            ChartKind::Bar => "Bar Chart",
+            ChartKind::Line => "Line Chart",
+            ChartKind::Scatter => "Scatter Chart",
+            ChartKind::Sparkline => "Sparkline",
+        }
+    }
+}
+
+/// Points of a line or scatter chart
+/// With a single column, x is the row number and the column is the only series
+/// With several columns, the first column gives x and every other column is a series
+/// Series are named after their column label, empty and error cells are left out
+/// Cells only hold numbers, so a header row cannot name the series: it would be plotted as data
+/// instead, and the axis titles come from the column labels too
+pub struct ChartData {
+    pub x_title: String,
+    pub series: Vec<(String, Vec<(f64, f64)>)>,
+}
+
+impl ChartData {
+    /// Gathers the points of the block with corners start and end (inclusive)
+    pub fn from_range(db: &Database, start: (usize, usize), end: (usize, usize)) -> ChartData {
+        let (row_low, row_high) = (start.0.min(end.0), start.0.max(end.0));
+        let (col_low, col_high) = (start.1.min(end.1), start.1.max(end.1));
+
+        let value = |row: usize, col: usize| match db.get((1000 * col + row) as u32) {
+            Ok(CellData::IntData(i)) => Some(*i as f64),
+            Ok(CellData::FloatData(f)) => Some(*f as f64),
+            _ => None,
+        };
+
+        let (x_title, first_series) = if col_low == col_high {
+            ("Row".to_string(), col_low)
+        } else {
+            (generate_column_label(col_low as u32), col_low + 1)
+        };
+
+        let series = (first_series..=col_high)
+            .map(|col| {
+                let points = (row_low..=row_high)
+                    .filter_map(|row| {
+                        let x = if col_low == col_high {
+                            Some(row as f64 + 1.0)
+                        } else {
+                            value(row, col_low)
+                        };
+                        Some((x?, value(row, col)?))
+                    })
+                    .collect();
+                (generate_column_label(col as u32), points)
+            })
+            .collect();
+
+        ChartData { x_title, series }
+    }
+
+    /// Smallest and largest x and y over all series, padded when they are equal
+    /// Returns ((x_min, x_max), (y_min, y_max)), or None if there are no points
+    pub fn bounds(&self) -> Option<((f64, f64), (f64, f64))> {
+        let points = self.series.iter().flat_map(|(_, points)| points.iter());
+        let mut bounds: Option<((f64, f64), (f64, f64))> = None;
+        for &(x, y) in points {
+            bounds = Some(match bounds {
+                None => ((x, x), (y, y)),
+                Some(((x0, x1), (y0, y1))) => ((x0.min(x), x1.max(x)), (y0.min(y), y1.max(y))),
+            });
+        }
+
+        let pad = |(low, high): (f64, f64)| {
+            if low == high {
+                (low - 1.0, high + 1.0)
+            } else {
+                (low, high)
+            }
+        };
+        bounds.map(|(x, y)| (pad(x), pad(y)))
+    }
+}
+
+/// Heights of a sparkline, negative and fractional values are kept by drawing each value as its position
+/// between the smallest and largest value
+/// Returns (min, max, heights from 1 to 100), or None if there are no values
+pub fn sparkline_data(values: &[f32]) -> Option<(f32, f32, Vec<u64>)> {
+    if values.is_empty() {
+        return None;
+    }
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+    let heights = values
+        .iter()
+        .map(|v| {
+            if max > min {
+                (1.0 + 99.0 * (v - min) / (max - min)).round() as u64
+            } else {
+                50
+            }
+        })
+        .collect();
+    Some((min, max, heights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::app::{App, Mode};
+    use crate::utils::extract_range_data;
+
+    #[test]
+    fn test_single_column() {
+        let mut db = Database::new(10, 10);
+        let _ = db.set_int(1001, -3);
+        let _ = db.set_float(1003, 2.5);
+
+        let data = ChartData::from_range(&db, (0, 1), (3, 1));
+        assert_eq!(data.x_title, "Row");
+        assert_eq!(
+            data.series,
+            vec![("B".to_string(), vec![(2.0, -3.0), (4.0, 2.5)])]
+        );
+        assert_eq!(data.bounds(), Some(((2.0, 4.0), (-3.0, 2.5))));
+    }
+
+    #[test]
+    fn test_several_columns() {
+        let mut db = Database::new(10, 10);
+        for row in 0..3 {
+            let _ = db.set_int(row, row as i32 * 10);
+            let _ = db.set_int(1000 + row, row as i32);
+        }
+        let _ = db.set_int(2001, 7);
+
+        let data = ChartData::from_range(&db, (2, 2), (0, 0));
+        assert_eq!(data.x_title, "A");
+        assert_eq!(data.series.len(), 2);
+        assert_eq!(data.series[0].0, "B");
+        assert_eq!(data.series[0].1, vec![(0.0, 0.0), (10.0, 1.0), (20.0, 2.0)]);
+        assert_eq!(data.series[1].1, vec![(10.0, 7.0)]);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let db = Database::new(10, 10);
+        assert!(ChartData::from_range(&db, (0, 0), (2, 0))
+            .bounds()
+            .is_none());
+
+        let data = ChartData {
+            x_title: String::new(),
+            series: vec![("A".to_string(), vec![(1.0, 5.0)])],
+        };
+        assert_eq!(data.bounds(), Some(((0.0, 2.0), (4.0, 6.0))));
+    }
+
+    #[test]
+    fn test_sparkline_data() {
+        assert_eq!(
+            sparkline_data(&[-1.0, 0.5, 2.0]),
+            Some((-1.0, 2.0, vec![1, 51, 100]))
+        );
+        assert_eq!(sparkline_data(&[3.0, 3.0]), Some((3.0, 3.0, vec![50, 50])));
+        assert_eq!(sparkline_data(&[]), None);
+
+        // A selection of errors only has nothing to plot
+        let mut app = App::new(Database::new(5, 5));
+        let _ = app.db.set_int(0, 1);
+        let _ = app.db.set_error(0, true);
+        app.mode = Mode::Graph((0, 0), (1, 0));
+        let values: Vec<f32> = extract_range_data(&app).iter().map(|(_, v)| *v).collect();
+        assert_eq!(sparkline_data(&values), None);
+    }
+
+    #[test]
+    fn test_next() {
+        let mut kind = ChartKind::Bar;
+        for _ in 0..4 {
+            kind = kind.next();
+        }
+        assert_eq!(kind, ChartKind::Bar);
+    }
+}
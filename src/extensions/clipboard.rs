@@ -0,0 +1,197 @@
+use crate::database::cell::{Cell, CellData};
+use crate::database::Database;
+use crate::evaluator::{assign, assign_data, clear, clear_range, recalc};
+use crate::utils::dep_to_response;
+
+/// Internal register holding a rectangular block of cells
+/// cells are stored row-major, None for empty cells
+/// origin is the top-left corner the block was copied from, used to shift references on paste
+pub struct Clipboard {
+    origin: (usize, usize),
+    cols: usize,
+    cells: Vec<Option<Cell>>,
+}
+
+impl Clipboard {
+    /// Copies the block with corners start and end (inclusive, in any order)
+    pub fn copy(db: &Database, start: (usize, usize), end: (usize, usize)) -> Self {
+        let (row_low, row_high) = (start.0.min(end.0), start.0.max(end.0));
+        let (col_low, col_high) = (start.1.min(end.1), start.1.max(end.1));
+
+        let mut cells = Vec::with_capacity((row_high - row_low + 1) * (col_high - col_low + 1));
+        for row in row_low..=row_high {
+            for col in col_low..=col_high {
+                cells.push(db.get_cell_clone((1000 * col + row) as u32).ok());
+            }
+        }
+
+        Self {
+            origin: (row_low, col_low),
+            cols: col_high - col_low + 1,
+            cells,
+        }
+    }
+
+    /// Copies the block and then empties it in the database
+    pub fn cut(db: &mut Database, start: (usize, usize), end: (usize, usize)) -> Self {
+        let clipboard = Self::copy(db, start, end);
+
+        clear_range(
+            db,
+            (1000 * start.1 + start.0) as u32,
+            (1000 * end.1 + end.0) as u32,
+        );
+
+        clipboard
+    }
+
+    /// Pastes the block with its top-left corner at `at`
+    /// Formula references are shifted by the distance between `at` and the copied origin
+    /// All pasted cells are recalculated together once every cell is written
+    /// Returns 0, or the error code of the last cell that could not be pasted
+    pub fn paste(&self, db: &mut Database, at: (usize, usize)) -> i32 {
+        let drow = at.0 as i64 - self.origin.0 as i64;
+        let dcol = at.1 as i64 - self.origin.1 as i64;
+
+        let mut ec = 0;
+        let mut pasted = Vec::with_capacity(self.cells.len());
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            let row = at.0 + i / self.cols;
+            let col = at.1 + i % self.cols;
+            let cell_idx = (1000 * col + row) as u32;
+
+            if !db.cell_in_range(cell_idx) {
+                ec = 4;
+                continue;
+            }
+
+            let res = paste_cell(db, cell_idx, cell.as_ref(), drow, dcol);
+
+            if res != 0 {
+                ec = res;
+            } else {
+                pasted.push(cell_idx);
+            }
+        }
+
+        recalc(db, &pasted);
+        ec
+    }
+}
+
+/// Writes cell (None for an empty cell) into cell_idx without recalculating
+/// Formula references are shifted by drow rows and dcol columns
+/// Returns the evaluator error code
+pub fn paste_cell(
+    db: &mut Database,
+    cell_idx: u32,
+    cell: Option<&Cell>,
+    drow: i64,
+    dcol: i64,
+) -> i32 {
+    match cell {
+        Some(cell) => match cell.get_dep() {
+            Some(dep) => match dep
+                .shifted(drow, dcol)
+                .and_then(|dep| dep_to_response(cell_idx, dep))
+            {
+                Some(r) => assign(r, db),
+                None => 4,
+            },
+            None => {
+                // Constants keep their value, including the error state of e.g. =1/0
+                let data = match cell.get_data() {
+                    Ok(&data) => data,
+                    Err(()) => CellData::IntData(0),
+                };
+                let res = assign_data(db, cell_idx, data);
+                if cell.has_error() {
+                    let _ = db.set_error(cell_idx, true);
+                }
+                res
+            }
+        },
+        None => clear(db, cell_idx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::run_command;
+
+    #[test]
+    fn test_copy_paste_values() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=5");
+        run_command(&mut db, "B1=7");
+
+        let clipboard = Clipboard::copy(&db, (0, 1), (0, 0));
+        assert_eq!((clipboard.cells.len(), clipboard.cols), (2, 2));
+
+        assert_eq!(clipboard.paste(&mut db, (3, 2)), 0);
+        assert!(db.get(2003) == Ok(&CellData::IntData(5)));
+        assert!(db.get(3003) == Ok(&CellData::IntData(7)));
+    }
+
+    #[test]
+    fn test_paste_shifts_references() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=2");
+        run_command(&mut db, "A2=3");
+        run_command(&mut db, "B1=A1*10");
+
+        let clipboard = Clipboard::copy(&db, (0, 1), (0, 1));
+        assert_eq!(clipboard.paste(&mut db, (1, 1)), 0);
+        assert!(db.get(1001) == Ok(&CellData::IntData(30)));
+
+        // The pasted formula must be live
+        run_command(&mut db, "A2=4");
+        assert!(db.get(1001) == Ok(&CellData::IntData(40)));
+    }
+
+    #[test]
+    fn test_paste_range_formula() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=1");
+        run_command(&mut db, "A2=2");
+        run_command(&mut db, "B1=1");
+        run_command(&mut db, "B2=2");
+        run_command(&mut db, "A3=SUM(A1:A2)");
+        run_command(&mut db, "A4=A3+1");
+
+        let clipboard = Clipboard::copy(&db, (2, 0), (3, 0));
+        assert_eq!(clipboard.paste(&mut db, (2, 1)), 0);
+        assert!(db.get(1002) == Ok(&CellData::FloatData(3.0)));
+        assert!(db.get(1003) == Ok(&CellData::FloatData(4.0)));
+    }
+
+    #[test]
+    fn test_paste_out_of_range() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "B2=A1+1");
+
+        // Shifting A1 one column to the left leaves the sheet
+        let clipboard = Clipboard::copy(&db, (1, 1), (1, 1));
+        assert_eq!(clipboard.paste(&mut db, (1, 0)), 4);
+
+        // Pasting past the last row
+        let clipboard = Clipboard::copy(&db, (0, 0), (1, 0));
+        assert_eq!(clipboard.paste(&mut db, (9, 0)), 4);
+    }
+
+    #[test]
+    fn test_cut() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=5");
+        run_command(&mut db, "B1=A1+1");
+
+        let clipboard = Clipboard::cut(&mut db, (0, 0), (0, 0));
+        assert!(db.is_cell_initialized(0) == Ok(false));
+        assert!(db.get(1000) == Ok(&CellData::IntData(1)));
+
+        assert_eq!(clipboard.paste(&mut db, (4, 4)), 0);
+        assert!(db.get(4004) == Ok(&CellData::IntData(5)));
+    }
+}
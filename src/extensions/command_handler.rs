@@ -1,5 +1,104 @@
+use crate::database::format::NumberFormat;
+use crate::database::Database;
+use crate::display::cell_index;
 use crate::extensions::app::{App, AppCommand, Mode};
+use crate::extensions::cond_format::CondFormat;
+use crate::extensions::filter::Filter;
+use crate::extensions::native::{load_from_sheet, save_to_sheet, EXTENSION};
+use crate::extensions::search::{parse_substitution, replace};
+use crate::extensions::sort::{parse_keys, sort_rows};
+use crate::extensions::svg::export_chart;
+use crate::extensions::swap::{has_swap, load_swap, remove_swap, swap_path, write_swap};
+use crate::extensions::theme::Theme;
 use crate::utils::{load_from_csv, save_to_csv};
+use std::error::Error;
+
+/// Checks if path is a .csv or native file
+fn supported_file(path: &str) -> bool {
+    path.ends_with(".csv") || path.ends_with(EXTENSION)
+}
+
+/// Saves in the native format or as CSV, depending on the extension of path
+/// formatted writes CSV values in their number format
+fn save_file(app: &App, path: &str, formatted: bool) -> Result<(), Box<dyn Error>> {
+    if path.ends_with(EXTENSION) {
+        save_to_sheet(&app.db, &app.cond_formats, path)
+    } else {
+        save_to_csv(&app.db, path, formatted)
+    }
+}
+
+/// Loads a native or CSV file, depending on the extension of path
+/// Returns the sheet and its conditional formatting rules, CSV files have none
+fn load_file(path: &str) -> Result<(Database, Vec<CondFormat>), Box<dyn Error>> {
+    if path.ends_with(EXTENSION) {
+        load_from_sheet(path)
+    } else {
+        Ok((load_from_csv(path)?, Vec::new()))
+    }
+}
+
+/// Logs that the sheet was saved to path
+/// The unsaved changes are only cleared if path is the open file and it holds the whole sheet:
+/// CSV files drop number formats, column widths and conditional formatting rules
+/// The swap file of the open file is not needed anymore once they are cleared
+fn saved(app: &mut App, path: &str) {
+    if path != app.file_name {
+        app.log(&format!("Saved {}", path));
+        return;
+    }
+
+    let dropped = !app.db.formats().is_empty()
+        || !app.db.col_widths().is_empty()
+        || !app.cond_formats.is_empty();
+    if !path.ends_with(EXTENSION) && dropped {
+        app.log(&format!(
+            "Saved {} without its formats, column widths and highlight rules, use a {} file to keep them",
+            path, EXTENSION
+        ));
+        return;
+    }
+
+    app.mark_saved();
+    remove_swap(&app.file_name);
+    app.log(&format!("Saved {}", path));
+}
+
+/// Writes the unsaved changes of the open file, if any, to its swap file
+/// A failure is only reported in Normal mode, so that a line being edited is not lost
+pub fn autosave(app: &mut App) {
+    if app.file_name.is_empty() || !app.is_dirty() {
+        return;
+    }
+    if write_swap(&app.db, &app.cond_formats, &app.file_name).is_ok() {
+        app.log(&format!("Autosaved to {}", swap_path(&app.file_name)));
+    } else if app.mode == Mode::Normal {
+        app.mode = Mode::ErrMsg("Autosave failed");
+    } else {
+        app.log("Autosave failed");
+    }
+}
+
+/// Answers the offer to recover the swap file of the open file, made by :o
+/// Recovered changes are unsaved until the file is written, a refused swap file is deleted
+pub fn recover(app: &mut App, accept: bool) {
+    app.mode = Mode::Normal;
+    if !accept {
+        remove_swap(&app.file_name);
+        app.log(&format!("Discarded {}", swap_path(&app.file_name)));
+        return;
+    }
+
+    match load_swap(&app.file_name) {
+        Ok((db, cond_formats)) => {
+            app.db = db;
+            app.cond_formats = cond_formats;
+            app.dirty = true;
+            app.log(&format!("Recovered {}", swap_path(&app.file_name)));
+        }
+        Err(_) => app.mode = Mode::ErrMsg("Failed to recover the swap file"),
+    }
+}
 
 pub fn handle(app: &mut App, ip: String) -> std::io::Result<Option<AppCommand>> {
     let args: Vec<&str> = ip.split_ascii_whitespace().collect();
@@ -8,19 +107,280 @@ pub fn handle(app: &mut App, ip: String) -> std::io::Result<Option<AppCommand>>
         return Ok(None);
     }
 
+    if ip.starts_with("s/") {
+        let (old, new) = match parse_substitution(&ip) {
+            Some(sub) => sub,
+            None => {
+                app.mode = Mode::ErrMsg("Substitution must look like :s/old/new/");
+                return Ok(None);
+            }
+        };
+
+        // Substitutes in the selection, or in the current cell
+        let (start, end) = app.cmd_selection.unwrap_or((app.selected, app.selected));
+        let (changed, ec) = replace(&mut app.db, start, end, old, new);
+        if ec != 0 {
+            app.mode = Mode::ErrMsg(crate::ERRMSG[ec as usize]);
+            return Ok(None);
+        }
+        if changed == 0 {
+            app.mode = Mode::ErrMsg("Pattern not found");
+            return Ok(None);
+        }
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "filter" {
+        if args.len() == 2 && args[1] == "off" {
+            app.set_filter(None);
+        } else {
+            // The table is the selection, or the whole sheet
+            let ((row_low, col_low), (row_high, col_high)) = app.cmd_selection.unwrap_or((
+                (0, 0),
+                (app.db.num_rows as usize - 1, app.db.num_cols as usize - 1),
+            ));
+
+            match Filter::parse(
+                &ip.trim_start()[6..],
+                (row_low, row_high),
+                (col_low, col_high),
+            ) {
+                Ok(filter) => app.set_filter(Some(filter)),
+                Err(msg) => {
+                    app.mode = Mode::ErrMsg(msg);
+                    return Ok(None);
+                }
+            }
+        }
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "goto" {
+        let (row, col) = match args.get(1).and_then(|label| cell_index(label)) {
+            Some((row, col)) if args.len() == 2 => (row as usize, col as usize),
+            _ => {
+                app.mode = Mode::ErrMsg("Go to a cell with :goto B250");
+                return Ok(None);
+            }
+        };
+
+        if row >= app.db.num_rows as usize || col >= app.db.num_cols as usize {
+            app.mode = Mode::ErrMsg(crate::ERRMSG[4]);
+            return Ok(None);
+        }
+        if !app.row_visible(row) {
+            app.mode = Mode::ErrMsg("Row is hidden by the filter");
+            return Ok(None);
+        }
+
+        app.jump_to(row, col);
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "colwidth" || args[0] == "autofit" {
+        // Acts on the columns of the selection, or on the current column
+        if args[0] == "autofit" && args.len() == 1 {
+            app.autofit();
+        } else {
+            match args.get(1).map(|w| w.parse::<u16>()) {
+                Some(Ok(width)) if args.len() == 2 && width > 0 => app.set_col_width(width),
+                _ => {
+                    app.mode = Mode::ErrMsg("Set a width with :colwidth 20, or use :autofit");
+                    return Ok(None);
+                }
+            }
+        }
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "format" {
+        // Formats the selection, or the current cell
+        let format = match args.get(1) {
+            Some(&"off") if args.len() == 2 => None,
+            Some(pattern) if args.len() == 2 => match NumberFormat::parse(pattern) {
+                Some(format) => Some(format),
+                None => {
+                    app.mode = Mode::ErrMsg(
+                        "Invalid format, use e.g. 0.000, #,##0.00, 0.0%, $#,##0.00 or 0.00E+00",
+                    );
+                    return Ok(None);
+                }
+            },
+            _ => {
+                app.mode = Mode::ErrMsg("Set a format with :format 0.000, or :format off");
+                return Ok(None);
+            }
+        };
+        app.set_format(format);
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "cf" {
+        // Rules apply to the selection, or to the current cell
+        let (start, end) = app.cmd_selection.unwrap_or((app.selected, app.selected));
+        if args.len() == 2 && args[1] == "clear" {
+            app.cond_formats.retain(|cf| !cf.overlaps(start, end));
+        } else {
+            match CondFormat::parse(&ip.trim_start()[2..], start, end) {
+                Ok(cf) => app.cond_formats.push(cf),
+                Err(msg) => {
+                    app.mode = Mode::ErrMsg(msg);
+                    return Ok(None);
+                }
+            }
+        }
+        app.dirty = true;
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "help" && args.len() == 1 {
+        app.input_buffer.clear();
+        app.mode = Mode::Help(0);
+        return Ok(None);
+    }
+
+    if args[0] == "messages" && args.len() == 1 {
+        app.input_buffer.clear();
+        app.mode = Mode::Messages(0);
+        return Ok(None);
+    }
+
+    if args[0] == "theme" {
+        match args[1..] {
+            [name] => match Theme::by_name(name) {
+                Some(theme) => app.theme = theme,
+                None => {
+                    app.mode = Mode::ErrMsg("Unknown theme, use dark, light or high-contrast");
+                    return Ok(None);
+                }
+            },
+            _ => {
+                app.mode = Mode::ErrMsg("Pick a theme with :theme light");
+                return Ok(None);
+            }
+        }
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "freeze" {
+        let frozen = match args[1..] {
+            ["off"] => Some((0, 0)),
+            [rows, cols] => match (rows.parse::<usize>(), cols.parse::<usize>()) {
+                (Ok(rows), Ok(cols))
+                    if rows < app.db.num_rows as usize && cols < app.db.num_cols as usize =>
+                {
+                    Some((rows, cols))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match frozen {
+            Some((rows, cols)) => app.set_frozen(rows, cols),
+            None => {
+                app.mode = Mode::ErrMsg("Freeze rows and columns with :freeze 1 1, or :freeze off");
+                return Ok(None);
+            }
+        }
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
+    if args[0] == "export-chart" {
+        let (start, end) = match app.cmd_selection {
+            Some(range) => range,
+            None => {
+                app.mode = Mode::ErrMsg("Open the chart of a selection with g, then press :");
+                return Ok(None);
+            }
+        };
+        if args.len() != 2 || !args[1].ends_with(".svg") {
+            app.mode = Mode::ErrMsg("Export the chart with :export-chart file.svg");
+            return Ok(None);
+        }
+
+        // Back to the chart, which is what gets exported
+        app.mode = Mode::Graph(start, end);
+        app.input_buffer.clear();
+        if export_chart(app, args[1]).is_err() {
+            app.mode = Mode::ErrMsg("Failed to export chart");
+        }
+        return Ok(None);
+    }
+
+    if args[0] == "sort" {
+        let ((row_low, col_low), (row_high, col_high)) = match app.cmd_selection {
+            Some(range) => range,
+            None => {
+                app.mode = Mode::ErrMsg("Select a range to sort, then press :");
+                return Ok(None);
+            }
+        };
+
+        let keys = match parse_keys(&ip.trim_start()[4..], col_low, col_high) {
+            Ok(keys) => keys,
+            Err(msg) => {
+                app.mode = Mode::ErrMsg(msg);
+                return Ok(None);
+            }
+        };
+
+        let ec = sort_rows(&mut app.db, (row_low, col_low), (row_high, col_high), &keys);
+        if ec != 0 {
+            app.mode = Mode::ErrMsg(crate::ERRMSG[ec as usize]);
+            return Ok(None);
+        }
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
     if args.len() == 1 {
         if args[0] == "q" {
+            if app.is_dirty() {
+                app.mode = Mode::ErrMsg("unsaved changes (use :q! to discard)");
+                return Ok(None);
+            }
+            return Ok(Some(AppCommand::Exit));
+        } else if args[0] == "q!" || (args[0] == "x" && !app.is_dirty()) {
+            // :q! drops the unsaved changes, :x has nothing to save
             return Ok(Some(AppCommand::Exit));
         } else if args[0] == "w" {
             if app.file_name == "" {
                 app.mode = Mode::ErrMsg(
-                    "NO FILE OPENED, GIVE A PATH TO SAVE, :w [file_path], file must be csv",
+                    "NO FILE OPENED, GIVE A PATH TO SAVE, :w [file_path], file must be csv or sheet",
                 );
                 return Ok(None);
             } else {
-                let res = save_to_csv(&app.db, &app.file_name);
+                let res = save_file(app, &app.file_name, false);
                 match res {
                     Ok(()) => {
+                        let file = app.file_name.clone();
+                        saved(app, &file);
                         app.mode = Mode::Normal;
                         app.input_buffer.clear();
                     }
@@ -30,14 +390,15 @@ pub fn handle(app: &mut App, ip: String) -> std::io::Result<Option<AppCommand>>
                     }
                 }
             }
-        } else if args[0] == "wq" {
+        } else if args[0] == "wq" || args[0] == "x" {
+            // :x only gets here with unsaved changes
             if app.file_name == "" {
                 app.mode = Mode::ErrMsg(
-                    "NO FILE OPENED, GIVE A PATH TO SAVE, :w [file_path], file must be csv",
+                    "NO FILE OPENED, GIVE A PATH TO SAVE, :w [file_path], file must be csv or sheet",
                 );
                 return Ok(None);
             } else {
-                let res = save_to_csv(&app.db, &app.file_name);
+                let res = save_file(app, &app.file_name, false);
                 match res {
                     Ok(()) => {
                         app.input_buffer.clear();
@@ -57,34 +418,77 @@ pub fn handle(app: &mut App, ip: String) -> std::io::Result<Option<AppCommand>>
         return Ok(None);
     }
 
+    if args.len() == 3 && args[0] == "w" && args[2] == "formatted" {
+        // Exports the values as displayed, in their number formats
+        if !args[1].ends_with(".csv") {
+            app.mode = Mode::ErrMsg("Formatted export is only for csv files");
+            return Ok(None);
+        }
+
+        if save_file(app, args[1], true).is_err() {
+            app.mode = Mode::ErrMsg("Failed to save file");
+            return Ok(None);
+        }
+        app.log(&format!("Saved {}", args[1]));
+
+        app.input_buffer.clear();
+        app.mode = Mode::Normal;
+        return Ok(None);
+    }
+
     if args.len() == 2 {
         if args[0] == "w" {
-            if !args[1].ends_with(".csv") {
-                app.mode = Mode::ErrMsg("File must be csv");
+            if !supported_file(args[1]) {
+                app.mode = Mode::ErrMsg("File must be .csv or .sheet");
                 return Ok(None);
             }
 
-            match save_to_csv(&app.db, args[1]) {
-                Ok(_) => {}
+            match save_file(app, args[1], false) {
+                Ok(_) => {
+                    // Without an open file, the written file becomes the open one
+                    if app.file_name.is_empty() {
+                        app.file_name = args[1].to_string();
+                    }
+                    saved(app, args[1]);
+                    app.input_buffer.clear();
+                    app.mode = Mode::Normal;
+                }
                 Err(_) => {
                     app.mode = Mode::ErrMsg("Failed to save file");
                     return Ok(None);
                 }
             };
-        } else if args[0] == "o" {
-            if !args[1].ends_with(".csv") {
-                app.mode = Mode::ErrMsg("File must be csv");
+        } else if args[0] == "o" || args[0] == "o!" {
+            if args[0] == "o" && app.is_dirty() {
+                app.mode = Mode::ErrMsg("unsaved changes (use :o! to discard)");
+                return Ok(None);
+            }
+            if !supported_file(args[1]) {
+                app.mode = Mode::ErrMsg("File must be .csv or .sheet");
                 return Ok(None);
             }
 
-            match load_from_csv(args[1]) {
-                Ok(db) => {
+            match load_file(args[1]) {
+                Ok((db, cond_formats)) => {
+                    // Changes dropped with :o! do not need to be recovered later
+                    if app.is_dirty() && !app.file_name.is_empty() {
+                        remove_swap(&app.file_name);
+                    }
                     app.file_name = args[1].to_string();
                     app.db = db;
+                    app.cond_formats = cond_formats;
                     app.selected = (0, 0);
                     app.topleft = (0, 0);
+                    app.frozen = (0, 0);
+                    app.filter = None;
+                    app.mark_saved();
                     app.input_buffer.clear();
                     app.mode = Mode::Normal;
+                    app.log(&format!("Opened {}", args[1]));
+                    if has_swap(args[1]) {
+                        // Left by a session that did not exit cleanly
+                        app.mode = Mode::Recover;
+                    }
                 }
                 Err(_) => {
                     app.mode = Mode::ErrMsg("Failed to load file");
@@ -102,3 +506,90 @@ pub fn handle(app: &mut App, ip: String) -> std::io::Result<Option<AppCommand>>
     app.mode = Mode::ErrMsg("Unrecognized command");
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("command_test_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn run(app: &mut App, cmd: &str) -> Option<AppCommand> {
+        app.set_input(&format!(":{}", cmd));
+        handle(app, cmd.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_unsaved_changes() {
+        let (csv, sheet) = (temp_path("a.csv"), temp_path("b.sheet"));
+        let mut app = App::new(Database::new(10, 10));
+        let _ = app.db.set_int(0, 1);
+
+        // Without an open file, the written file becomes the open one
+        assert!(run(&mut app, &format!("w {}", csv)).is_none());
+        assert_eq!(app.file_name, csv);
+        assert!(!app.is_dirty());
+
+        // Writing another file leaves the open one unsaved
+        let _ = app.db.set_int(1, 2);
+        run(&mut app, &format!("w {}", sheet));
+        assert!(app.is_dirty());
+        assert!(run(&mut app, "q").is_none());
+        assert_eq!(
+            app.mode,
+            Mode::ErrMsg("unsaved changes (use :q! to discard)")
+        );
+        assert!(run(&mut app, &format!("o {}", sheet)).is_none());
+        assert_eq!(app.file_name, csv);
+
+        // CSV files cannot hold column widths
+        app.db.set_col_width(0, Some(20));
+        run(&mut app, "w");
+        assert!(app.is_dirty());
+        app.db.set_col_width(0, None);
+        run(&mut app, "w");
+        assert!(!app.is_dirty());
+        assert!(matches!(run(&mut app, "x"), Some(AppCommand::Exit)));
+
+        let _ = app.db.set_int(1, 3);
+        run(&mut app, &format!("o! {}", sheet));
+        assert_eq!(app.file_name, sheet);
+        assert!(!app.is_dirty());
+        assert!(app.db.get(1) == Ok(&crate::database::cell::CellData::IntData(2)));
+
+        let _ = std::fs::remove_file(&csv);
+        let _ = std::fs::remove_file(&sheet);
+    }
+
+    #[test]
+    fn test_swap_kept() {
+        let (csv, sheet) = (temp_path("c.csv"), temp_path("d.sheet"));
+        let mut app = App::new(Database::new(10, 10));
+        run(&mut app, &format!("w {}", csv));
+        let _ = app.db.set_int(0, 1);
+        autosave(&mut app);
+        assert!(has_swap(&csv));
+
+        // Neither writing another file nor a refused :o drops the swap file
+        run(&mut app, &format!("w {}", sheet));
+        run(&mut app, &format!("o {}", sheet));
+        assert!(has_swap(&csv));
+
+        // Discarding the changes drops it
+        run(&mut app, &format!("o! {}", sheet));
+        assert!(!has_swap(&csv));
+
+        let _ = app.db.set_int(0, 2);
+        autosave(&mut app);
+        assert!(has_swap(&sheet));
+        run(&mut app, "w");
+        assert!(!has_swap(&sheet));
+
+        let _ = std::fs::remove_file(&csv);
+        let _ = std::fs::remove_file(&sheet);
+    }
+}
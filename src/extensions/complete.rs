@@ -0,0 +1,244 @@
+use crate::extensions::theme::THEMES;
+use std::fs;
+use std::path::Path;
+
+/// Commands of the ':' command line, with an example and a description for the help screen
+pub const COMMANDS: [(&str, &str, &str); 19] = [
+    (
+        "autofit",
+        ":autofit",
+        "Fit the selected columns to their values",
+    ),
+    (
+        "cf",
+        ":cf red < 0",
+        "Highlight cells by rule, :cf clear removes the rules",
+    ),
+    (
+        "colwidth",
+        ":colwidth 20",
+        "Set the width of the selected columns",
+    ),
+    (
+        "export-chart",
+        ":export-chart chart.svg",
+        "Save the chart shown as SVG, from Graph mode",
+    ),
+    (
+        "filter",
+        ":filter C > 100",
+        "Show only matching rows, :filter off shows all",
+    ),
+    (
+        "format",
+        ":format #,##0.00",
+        "Set the number format, :format off removes it",
+    ),
+    (
+        "freeze",
+        ":freeze 1 1",
+        "Pin rows and columns, :freeze off unpins them",
+    ),
+    ("goto", ":goto B250", "Go to a cell"),
+    ("help", ":help", "Show this help"),
+    ("messages", ":messages", "Show the recent errors and saves"),
+    ("o", ":o data.csv", "Open a .csv or .sheet file"),
+    (
+        "o!",
+        ":o! data.csv",
+        "Open a file, dropping the unsaved changes",
+    ),
+    ("q", ":q", "Quit, refused with unsaved changes"),
+    ("q!", ":q!", "Quit without saving"),
+    (
+        "sort",
+        ":sort B desc, A asc",
+        "Sort the rows of the selection",
+    ),
+    (
+        "theme",
+        ":theme light",
+        "Change colors: dark, light or high-contrast",
+    ),
+    (
+        "w",
+        ":w data.sheet",
+        "Save, to the open file without a name",
+    ),
+    ("wq", ":wq", "Save the open file and quit"),
+    ("x", ":x", "Save the open file if changed, and quit"),
+];
+
+/// Functions accepted by the parser, with an example and a description for the help screen
+pub const FUNCTIONS: [(&str, &str, &str); 6] = [
+    ("AVG", "=AVG(A1:A10)", "Average of a range"),
+    ("MAX", "=MAX(A1:C5)", "Largest value of a range"),
+    ("MIN", "=MIN(A1:C5)", "Smallest value of a range"),
+    ("SLEEP", "=SLEEP(2)", "Waits 2 seconds, then gives 2"),
+    ("STDEV", "=STDEV(B1:B20)", "Standard deviation of a range"),
+    ("SUM", "=SUM(A1:B5)", "Sum of a range"),
+];
+
+/// Finds the completions of the word ending line, line being the text before the cursor
+/// command is true for the ':' command line (line starts with ':'), false for a formula
+/// Command names, file paths of :o, :w and :export-chart, theme names and function names in formulas are completed
+/// Returns the byte position where the word starts and the sorted candidates replacing it
+pub fn candidates(line: &str, command: bool) -> (usize, Vec<String>) {
+    if command {
+        let rest = match line.strip_prefix(':') {
+            Some(rest) => rest,
+            None => return (line.len(), vec![]),
+        };
+
+        if !rest.contains(char::is_whitespace) {
+            let names: Vec<&str> = COMMANDS.iter().map(|&(name, _, _)| name).collect();
+            return (1, with_prefix(&names, rest));
+        }
+
+        let start = line.rfind(char::is_whitespace).map_or(0, |idx| idx + 1);
+        match rest.split_whitespace().next() {
+            Some("o") | Some("o!") | Some("w") | Some("export-chart") => {
+                (start, file_candidates(&line[start..]))
+            }
+            Some("theme") => {
+                let names: Vec<&str> = THEMES.iter().map(|theme| theme.name).collect();
+                (start, with_prefix(&names, &line[start..]))
+            }
+            _ => (start, vec![]),
+        }
+    } else {
+        let start = line
+            .trim_end_matches(|c: char| c.is_ascii_uppercase())
+            .len();
+        let word = &line[start..];
+        if word.is_empty() {
+            return (start, vec![]);
+        }
+        let names: Vec<&str> = FUNCTIONS.iter().map(|&(name, _, _)| name).collect();
+        (start, with_prefix(&names, word))
+    }
+}
+
+fn with_prefix(words: &[&str], prefix: &str) -> Vec<String> {
+    words
+        .iter()
+        .filter(|word| word.starts_with(prefix))
+        .map(|word| word.to_string())
+        .collect()
+}
+
+/// Lists the entries of the directory of partial starting with its file name
+/// Directories get a trailing '/', so that completion can continue inside them
+fn file_candidates(partial: &str) -> Vec<String> {
+    let (dir, name) = match partial.rfind('/') {
+        Some(idx) => (&partial[..=idx], &partial[idx + 1..]),
+        None => ("", partial),
+    };
+
+    let dir_path = if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    };
+    let entries = match fs::read_dir(dir_path) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut res: Vec<String> = entries
+        .map_while(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            if !file_name.starts_with(name) || (name.is_empty() && file_name.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, slash))
+        })
+        .collect();
+    res.sort();
+    res
+}
+
+/// Longest common prefix of the candidates
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let first = match candidates.first() {
+        Some(first) => first.as_str(),
+        None => return "",
+    };
+
+    let len = candidates.iter().skip(1).fold(first.len(), |len, c| {
+        first[..len]
+            .char_indices()
+            .zip(c.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(c.len()), |((idx, _), _)| idx)
+    });
+    &first[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(candidates(":", true).1.len(), COMMANDS.len());
+        assert_eq!(candidates(":w", true), (1, vec!["w".into(), "wq".into()]));
+        assert_eq!(candidates(":go", true), (1, vec!["goto".into()]));
+        assert_eq!(candidates(":sort B", true), (6, vec![]));
+        assert_eq!(
+            candidates(":theme h", true),
+            (7, vec!["high-contrast".into()])
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            candidates("=S", false),
+            (1, vec!["SLEEP".into(), "STDEV".into(), "SUM".into()])
+        );
+        assert_eq!(
+            candidates("=MAX(A1:B2)+MI", false),
+            (12, vec!["MIN".into()])
+        );
+        assert_eq!(candidates("=A1+", false), (4, vec![]));
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("complete_test_{}", std::process::id()));
+        let _ = fs::create_dir_all(dir.join("sub"));
+        let _ = fs::write(dir.join("data.csv"), "");
+        let _ = fs::write(dir.join("dates.csv"), "");
+
+        let prefix = format!(":o {}/", dir.display());
+        let (start, found) = candidates(&(prefix.clone() + "da"), true);
+        assert_eq!(start, 3);
+        assert_eq!(
+            found,
+            vec![
+                format!("{}/data.csv", dir.display()),
+                format!("{}/dates.csv", dir.display())
+            ]
+        );
+        assert_eq!(
+            common_prefix(&found),
+            format!("{}/dat", dir.display()).as_str()
+        );
+
+        let (_, found) = candidates(&(prefix + "s"), true);
+        assert_eq!(found, vec![format!("{}/sub/", dir.display())]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(common_prefix(&["SUM".into()]), "SUM");
+        assert_eq!(common_prefix(&["STDEV".into(), "SUM".into()]), "S");
+        assert_eq!(common_prefix(&["w".into(), "wq".into()]), "w");
+    }
+}
@@ -0,0 +1,317 @@
+use crate::database::cell::CellData;
+use crate::database::Database;
+use crate::extensions::filter::FilterOp;
+use ratatui::style::{Color, Modifier, Style};
+use std::str::FromStr;
+
+/// Value a cell is compared to: a number, or the average of the rule's range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Number(f32),
+    Avg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    /// Applies style to the cells for which `value op operand` holds
+    Compare {
+        op: FilterOp,
+        operand: Operand,
+        style: Style,
+    },
+    /// Colors the background from low (smallest value of the range) through mid to high (largest value)
+    Scale(Color, Color, Color),
+}
+
+/// Conditional formatting rule attached to the block with corners start and end (inclusive)
+/// Rules only change how cells are drawn, empty and error cells are never formatted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CondFormat {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub rule: Rule,
+}
+
+impl CondFormat {
+    /// Parses a rule like "red < 0", "bold+bg:yellow > AVG" or "scale red yellow green" for the block
+    /// Styles are joined by '+': bold, italic, underline, a color for the text or bg:color
+    /// Returns an error message if the rule is invalid
+    pub fn parse(
+        s: &str,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Result<CondFormat, &'static str> {
+        let s = s.trim();
+        let (start, end) = (
+            (start.0.min(end.0), start.1.min(end.1)),
+            (start.0.max(end.0), start.1.max(end.1)),
+        );
+
+        if let Some(colors) = s.strip_prefix("scale ") {
+            let colors: Vec<Color> = colors
+                .split_whitespace()
+                .map(Color::from_str)
+                .collect::<Result<_, _>>()
+                .map_err(|_| "Invalid color in scale")?;
+            return match colors.as_slice() {
+                &[low, mid, high] => Ok(CondFormat {
+                    start,
+                    end,
+                    rule: Rule::Scale(low, mid, high),
+                }),
+                _ => Err("A scale needs three colors, e.g. :cf scale red yellow green"),
+            };
+        }
+
+        let op_start = s
+            .find(['<', '>', '=', '!'])
+            .ok_or("Rules must look like :cf red < 0")?;
+        let op_end = s[op_start..]
+            .find(|c| !['<', '>', '=', '!'].contains(&c))
+            .map_or(s.len(), |idx| op_start + idx);
+
+        let style = parse_style(s[..op_start].trim()).ok_or("Invalid style in rule")?;
+        let op = FilterOp::parse(&s[op_start..op_end])
+            .ok_or("Rule operator must be one of < <= > >= = !=")?;
+        let operand = match s[op_end..].trim() {
+            "AVG" => Operand::Avg,
+            value => Operand::Number(
+                value
+                    .parse::<f32>()
+                    .map_err(|_| "Rule value must be a number or AVG")?,
+            ),
+        };
+
+        Ok(CondFormat {
+            start,
+            end,
+            rule: Rule::Compare { op, operand, style },
+        })
+    }
+
+    /// Checks if the cell (row, col) is in the rule's block
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        (self.start.0..=self.end.0).contains(&row) && (self.start.1..=self.end.1).contains(&col)
+    }
+
+    /// Checks if the rule's block overlaps the block with corners start and end (inclusive)
+    pub fn overlaps(&self, start: (usize, usize), end: (usize, usize)) -> bool {
+        self.start.0 <= start.0.max(end.0)
+            && start.0.min(end.0) <= self.end.0
+            && self.start.1 <= start.1.max(end.1)
+            && start.1.min(end.1) <= self.end.1
+    }
+
+    /// Computes the (min, max, average) of the numbers in the rule's block, once per render
+    /// None if the block has no numbers
+    pub fn range_stats(&self, db: &Database) -> Option<(f32, f32, f32)> {
+        let mut values = Vec::new();
+        for col in self.start.1..=self.end.1 {
+            for row in self.start.0..=self.end.0 {
+                if let Some(value) = number(db.get((1000 * col + row) as u32)) {
+                    values.push(value);
+                }
+            }
+        }
+
+        if values.is_empty() {
+            return None;
+        }
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let avg = values.iter().sum::<f32>() / values.len() as f32;
+        Some((min, max, avg))
+    }
+
+    /// Style of a cell of the block holding data, stats being the result of range_stats
+    pub fn style(&self, data: Result<&CellData, bool>, stats: (f32, f32, f32)) -> Option<Style> {
+        let value = number(data)?;
+        let (min, max, avg) = stats;
+
+        match self.rule {
+            Rule::Compare { op, operand, style } => {
+                let operand = match operand {
+                    Operand::Number(n) => n,
+                    Operand::Avg => avg,
+                };
+                op.compare(value, operand).then_some(style)
+            }
+            Rule::Scale(low, mid, high) => {
+                let t = if max > min {
+                    (value - min) / (max - min)
+                } else {
+                    0.5
+                };
+                let bg = if t < 0.5 {
+                    mix(low, mid, 2.0 * t)
+                } else {
+                    mix(mid, high, 2.0 * t - 1.0)
+                };
+                Some(Style::default().fg(Color::Black).bg(bg))
+            }
+        }
+    }
+}
+
+/// Writes the rule in the syntax read by parse, without the block
+impl std::fmt::Display for CondFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.rule {
+            Rule::Compare { op, operand, style } => {
+                let mut parts = Vec::new();
+                for (modifier, name) in [
+                    (Modifier::BOLD, "bold"),
+                    (Modifier::ITALIC, "italic"),
+                    (Modifier::UNDERLINED, "underline"),
+                ] {
+                    if style.add_modifier.contains(modifier) {
+                        parts.push(name.to_string());
+                    }
+                }
+                if let Some(fg) = style.fg {
+                    parts.push(fg.to_string().to_lowercase());
+                }
+                if let Some(bg) = style.bg {
+                    parts.push(format!("bg:{}", bg.to_string().to_lowercase()));
+                }
+
+                let operand = match operand {
+                    Operand::Number(n) => n.to_string(),
+                    Operand::Avg => "AVG".to_string(),
+                };
+                write!(f, "{} {} {}", parts.join("+"), op, operand)
+            }
+            Rule::Scale(low, mid, high) => write!(
+                f,
+                "scale {} {} {}",
+                low.to_string().to_lowercase(),
+                mid.to_string().to_lowercase(),
+                high.to_string().to_lowercase()
+            ),
+        }
+    }
+}
+
+/// Parses styles joined by '+', see CondFormat::parse
+fn parse_style(s: &str) -> Option<Style> {
+    let mut style = Style::default();
+    for part in s.split('+') {
+        style = match part {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underline" => style.add_modifier(Modifier::UNDERLINED),
+            _ => match part.strip_prefix("bg:") {
+                Some(color) => style.bg(Color::from_str(color).ok()?),
+                None => style.fg(Color::from_str(part).ok()?),
+            },
+        };
+    }
+    Some(style)
+}
+
+fn number(data: Result<&CellData, bool>) -> Option<f32> {
+    match data {
+        Ok(CellData::IntData(i)) => Some(*i as f32),
+        Ok(CellData::FloatData(f)) => Some(*f),
+        _ => None,
+    }
+}
+
+/// RGB value of a color, named colors use the usual terminal palette
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 49, 49),
+        Color::Green => (13, 188, 121),
+        Color::Yellow => (229, 229, 16),
+        Color::Blue => (36, 114, 200),
+        Color::Magenta => (188, 63, 188),
+        Color::Cyan => (17, 168, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (102, 102, 102),
+        Color::LightRed => (241, 76, 76),
+        Color::LightGreen => (35, 209, 139),
+        Color::LightYellow => (245, 245, 67),
+        Color::LightBlue => (59, 142, 234),
+        Color::LightMagenta => (214, 112, 214),
+        Color::LightCyan => (41, 184, 219),
+        _ => (255, 255, 255),
+    }
+}
+
+/// Color at t (between 0 and 1) on the way from a to b
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    let (a, b) = (rgb(a), rgb(b));
+    let lerp = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t.clamp(0.0, 1.0)).round() as u8;
+    Color::Rgb(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cf = CondFormat::parse("red < 0", (4, 1), (0, 0)).unwrap();
+        assert_eq!((cf.start, cf.end), ((0, 0), (4, 1)));
+        assert_eq!(
+            cf.rule,
+            Rule::Compare {
+                op: FilterOp::Lt,
+                operand: Operand::Number(0.0),
+                style: Style::default().fg(Color::Red)
+            }
+        );
+
+        for rule in ["red < 0", "bold+bg:yellow > AVG", "scale red yellow green"] {
+            let cf = CondFormat::parse(rule, (0, 0), (1, 1)).unwrap();
+            assert_eq!(cf.to_string(), rule);
+        }
+        assert_eq!(
+            CondFormat::parse("bold>=AVG", (0, 0), (1, 1))
+                .unwrap()
+                .to_string(),
+            "bold >= AVG"
+        );
+
+        assert!(CondFormat::parse("red 0", (0, 0), (1, 1)).is_err());
+        assert!(CondFormat::parse("shiny < 0", (0, 0), (1, 1)).is_err());
+        assert!(CondFormat::parse("red < x", (0, 0), (1, 1)).is_err());
+        assert!(CondFormat::parse("scale red green", (0, 0), (1, 1)).is_err());
+    }
+
+    #[test]
+    fn test_style() {
+        let mut db = Database::new(10, 10);
+        let _ = db.set_int(0, -5);
+        let _ = db.set_int(1, 10);
+        let _ = db.set_int(2, 40);
+
+        let cf = CondFormat::parse("bold > AVG", (0, 0), (3, 0)).unwrap();
+        let stats = cf.range_stats(&db).unwrap();
+        assert_eq!(stats, (-5.0, 40.0, 15.0));
+        assert_eq!(cf.style(db.get(1), stats), None);
+        assert_eq!(
+            cf.style(db.get(2), stats),
+            Some(Style::default().add_modifier(Modifier::BOLD))
+        );
+        // Empty cells are never formatted
+        assert_eq!(cf.style(db.get(3), stats), None);
+
+        let cf = CondFormat::parse("scale #000000 #808080 #ffffff", (0, 0), (3, 0)).unwrap();
+        let bg = |data| cf.style(data, stats).unwrap().bg;
+        assert_eq!(bg(db.get(0)), Some(Color::Rgb(0, 0, 0)));
+        assert_eq!(bg(db.get(2)), Some(Color::Rgb(255, 255, 255)));
+        assert_eq!(bg(db.get(1)), Some(Color::Rgb(85, 85, 85)));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let cf = CondFormat::parse("red < 0", (2, 2), (4, 4)).unwrap();
+        assert!(cf.contains(3, 4));
+        assert!(!cf.contains(5, 4));
+        assert!(cf.overlaps((4, 0), (9, 2)));
+        assert!(!cf.overlaps((0, 0), (1, 9)));
+    }
+}
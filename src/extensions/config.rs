@@ -0,0 +1,231 @@
+use crate::extensions::app::{DEFAULT_COL_WIDTH, MAX_COL_WIDTH};
+use crate::extensions::keymap::{Action, Key, KeyMode, Keymap};
+use crate::extensions::theme::Theme;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Time between two writes of the swap file, unless the config changes it
+const DEFAULT_AUTOSAVE: Duration = Duration::from_secs(60);
+
+/// Settings read from the config file, see Config::parse
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub col_width: u16,
+    pub theme: Theme,
+    pub autosave: Option<Duration>,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            col_width: DEFAULT_COL_WIDTH,
+            theme: Theme::default(),
+            autosave: Some(DEFAULT_AUTOSAVE),
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Path of the config file, $XDG_CONFIG_HOME/spreadsheet/config.toml
+    /// or ~/.config/spreadsheet/config.toml, None if neither variable is set
+    pub fn path() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => Path::new(&std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("spreadsheet").join("config.toml"))
+    }
+
+    /// Reads the config file at path, a missing file gives the default config
+    pub fn load(path: &Path) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text),
+            Err(_) if !path.exists() => Ok(Config::default()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Parses a config file, written in a subset of TOML:
+    ///   col_width = 14        width of the columns that were never resized
+    ///   theme = "light"       color theme: dark (default), light or high-contrast
+    ///   autosave = 30         writes unsaved changes to the swap file every 30 seconds (60 by default), 0 turns it off
+    ///   [keys.normal]         keys of Normal mode, also keys.select, keys.graph, keys.stats and keys.help
+    ///   move_left = ["h", "Left"]
+    /// An action bound in a [keys] table loses its default keys, see Keymap::bind
+    /// Returns an error naming the line if the file is invalid
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+        let mut section: Option<KeyMode> = None;
+
+        for (line_no, line) in text.lines().enumerate() {
+            let bad_line = |msg: &str| format!("line {}: {}", line_no + 1, msg);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(
+                    name.trim()
+                        .strip_prefix("keys.")
+                        .and_then(KeyMode::from_name)
+                        .ok_or_else(|| bad_line("unknown table"))?,
+                );
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| bad_line("expected key = value"))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match (section, key) {
+                (None, "col_width") => {
+                    config.col_width = value
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|width| (1..=MAX_COL_WIDTH).contains(width))
+                        .ok_or_else(|| bad_line("col_width must be a number from 1 to 100"))?;
+                }
+                (None, "autosave") => {
+                    let secs = value
+                        .parse::<u64>()
+                        .map_err(|_| bad_line("autosave must be a number of seconds"))?;
+                    config.autosave = (secs > 0).then(|| Duration::from_secs(secs));
+                }
+                (None, "theme") => {
+                    config.theme = parse_string(value)
+                        .and_then(|name| Theme::by_name(&name))
+                        .ok_or_else(|| bad_line("unknown theme"))?;
+                }
+                (None, _) => return Err(bad_line("unknown setting")),
+                (Some(mode), action) => {
+                    let action =
+                        Action::from_name(action).ok_or_else(|| bad_line("unknown action"))?;
+                    let keys = parse_keys(value).ok_or_else(|| bad_line("invalid key"))?;
+                    if !config.keymap.bind(mode, action, keys) {
+                        return Err(bad_line("action is not available in this mode"));
+                    }
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Removes a comment starting with '#' outside of a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parses a string in double quotes
+fn parse_string(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    (!inner.contains('"')).then(|| inner.to_string())
+}
+
+/// Parses a key as a string, or a list of keys like ["h", "Left"]
+fn parse_keys(value: &str) -> Option<Vec<Key>> {
+    let names: Vec<String> = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(parse_string)
+            .collect::<Option<_>>()?,
+        None => vec![parse_string(value)?],
+    };
+    names.iter().map(|name| Key::parse(name)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "# Vim keys\n\
+             col_width = 20\n\
+             theme = \"high-contrast\"  # for bright rooms\n\
+             autosave = 30\n\
+             \n\
+             [keys.normal]\n\
+             move_left = [\"h\", \"Left\"]\n\
+             move_down = [\"j\", \"Down\"]\n\
+             [keys.select]\n\
+             exit = \"q\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.col_width, 20);
+        assert_eq!(config.theme.name, "high-contrast");
+        assert_eq!(config.autosave, Some(Duration::from_secs(30)));
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(
+            config.keymap.action(KeyMode::Normal, key('h')),
+            Some(Action::MoveLeft)
+        );
+        assert_eq!(
+            config.keymap.action(KeyMode::Normal, key('j')),
+            Some(Action::MoveDown)
+        );
+        assert_eq!(config.keymap.action(KeyMode::Normal, key('a')), None);
+        assert_eq!(
+            config.keymap.action(KeyMode::Select, key('q')),
+            Some(Action::Exit)
+        );
+        assert_eq!(
+            config.keymap.action(
+                KeyMode::Select,
+                KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)
+            ),
+            None
+        );
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(Config::default().autosave, Some(DEFAULT_AUTOSAVE));
+        assert_eq!(Config::parse("autosave = 0").unwrap().autosave, None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for (text, line) in [
+            ("col_width = 0", 1),
+            ("\ncolwidth = 10", 2),
+            ("theme = \"neon\"", 1),
+            ("autosave = soon", 1),
+            ("[keys.insert]", 1),
+            ("[keys.normal]\nfly = \"f\"", 2),
+            ("[keys.normal]\nmove_left = [\"h\", \"Lft\"]", 2),
+            ("[keys.graph]\npaste = \"p\"", 2),
+            ("[keys.normal]\nmove_left", 2),
+        ] {
+            let err = Config::parse(text).unwrap_err();
+            assert!(err.starts_with(&format!("line {}:", line)), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("config_test_{}.toml", std::process::id()));
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        fs::write(&path, "col_width = 8\n").unwrap();
+        assert_eq!(Config::load(&path).unwrap().col_width, 8);
+        let _ = fs::remove_file(&path);
+    }
+}
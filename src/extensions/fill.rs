@@ -0,0 +1,260 @@
+use crate::database::cell::CellData;
+use crate::database::Database;
+use crate::evaluator::{assign_data, recalc};
+use crate::extensions::clipboard::paste_cell;
+
+/// Fills every column of the block with corners start and end (inclusive) from its first row
+/// See fill_line for how each column is filled
+/// All filled cells are recalculated together once every cell is written
+/// Returns 0, or the error code of the last cell that could not be filled
+pub fn fill_down(db: &mut Database, start: (usize, usize), end: (usize, usize)) -> i32 {
+    let (row_low, row_high) = (start.0.min(end.0), start.0.max(end.0));
+    let (col_low, col_high) = (start.1.min(end.1), start.1.max(end.1));
+
+    let mut ec = 0;
+    let mut filled = Vec::new();
+    for col in col_low..=col_high {
+        let line: Vec<u32> = (row_low..=row_high)
+            .map(|row| (1000 * col + row) as u32)
+            .collect();
+        let res = fill_line(db, &line, (1, 0), &mut filled);
+        if res != 0 {
+            ec = res;
+        }
+    }
+
+    recalc(db, &filled);
+    ec
+}
+
+/// Same as fill_down, but fills every row of the block from its first column
+pub fn fill_right(db: &mut Database, start: (usize, usize), end: (usize, usize)) -> i32 {
+    let (row_low, row_high) = (start.0.min(end.0), start.0.max(end.0));
+    let (col_low, col_high) = (start.1.min(end.1), start.1.max(end.1));
+
+    let mut ec = 0;
+    let mut filled = Vec::new();
+    for row in row_low..=row_high {
+        let line: Vec<u32> = (col_low..=col_high)
+            .map(|col| (1000 * col + row) as u32)
+            .collect();
+        let res = fill_line(db, &line, (0, 1), &mut filled);
+        if res != 0 {
+            ec = res;
+        }
+    }
+
+    recalc(db, &filled);
+    ec
+}
+
+/// Fills a line of cells, step being the (row, col) distance between neighbours
+/// If the line starts with two or more constants forming a linear series, the series is extended
+/// Otherwise the first cell is copied along the line, shifting formula references by step each cell
+/// Written cells are pushed to filled, nothing is recalculated
+/// A series value that does not fit in an integer cell is not written and gives error code 2
+fn fill_line(db: &mut Database, line: &[u32], step: (i64, i64), filled: &mut Vec<u32>) -> i32 {
+    let mut ec = 0;
+
+    if let Some((seed_len, last, diff)) = linear_series(db, line) {
+        for (k, &cell_idx) in line.iter().enumerate().skip(seed_len) {
+            let n = (k + 1 - seed_len) as i32;
+            let res = match series_value(last, diff, n) {
+                Some(value) => assign_data(db, cell_idx, value),
+                None => 2,
+            };
+            if res != 0 {
+                ec = res;
+            } else {
+                filled.push(cell_idx);
+            }
+        }
+        return ec;
+    }
+
+    let first = db.get_cell_clone(line[0]).ok();
+
+    for (k, &cell_idx) in line.iter().enumerate().skip(1) {
+        let k = k as i64;
+        let res = paste_cell(db, cell_idx, first.as_ref(), k * step.0, k * step.1);
+        if res != 0 {
+            ec = res;
+        } else {
+            filled.push(cell_idx);
+        }
+    }
+
+    ec
+}
+
+/// Looks for a linear series among the constants at the start of line
+/// The seeds are the longest run of constants from the start following the difference of the first two,
+/// so values left further down the line, which are about to be overwritten, do not break the series
+/// Returns (number of seed cells, last seed value, common difference)
+/// None if the line does not start with two constants
+fn linear_series(db: &Database, line: &[u32]) -> Option<(usize, CellData, CellData)> {
+    let mut seeds = Vec::new();
+    for &cell_idx in line {
+        match db.get_cell(cell_idx) {
+            Ok(cell) if !cell.has_dep() => match cell.get_data() {
+                Ok(&data) => seeds.push(data),
+                Err(()) => break,
+            },
+            _ => break,
+        }
+    }
+
+    if seeds.len() < 2 {
+        return None;
+    }
+
+    let diff = difference(seeds[0], seeds[1])?;
+    let seed_len = 1 + seeds
+        .windows(2)
+        .take_while(|w| match (difference(w[0], w[1]), diff) {
+            (Some(CellData::IntData(a)), CellData::IntData(b)) => a == b,
+            (Some(a), b) => (as_f32(a) - as_f32(b)).abs() < 1e-4,
+            (None, _) => false,
+        })
+        .count();
+
+    Some((seed_len, seeds[seed_len - 1], diff))
+}
+
+/// Difference b - a of two seeds, None if it does not fit in an integer
+fn difference(a: CellData, b: CellData) -> Option<CellData> {
+    match (a, b) {
+        (CellData::IntData(a), CellData::IntData(b)) => b.checked_sub(a).map(CellData::IntData),
+        (a, b) => Some(b - a),
+    }
+}
+
+/// Value n steps of diff after last, None if it does not fit in an integer
+fn series_value(last: CellData, diff: CellData, n: i32) -> Option<CellData> {
+    match (last, diff) {
+        (CellData::IntData(last), CellData::IntData(diff)) => diff
+            .checked_mul(n)
+            .and_then(|step| last.checked_add(step))
+            .map(CellData::IntData),
+        (last, diff) => Some(last + diff * CellData::IntData(n)),
+    }
+}
+
+fn as_f32(data: CellData) -> f32 {
+    match data {
+        CellData::IntData(i) => i as f32,
+        CellData::FloatData(f) => f,
+        CellData::Empty => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::run_command;
+
+    #[test]
+    fn test_fill_down_formula() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=1");
+        run_command(&mut db, "A2=2");
+        run_command(&mut db, "A3=3");
+        run_command(&mut db, "B1=A1*2");
+
+        assert_eq!(fill_down(&mut db, (0, 1), (2, 1)), 0);
+        assert!(db.get(1001) == Ok(&CellData::IntData(4)));
+        assert!(db.get(1002) == Ok(&CellData::IntData(6)));
+
+        run_command(&mut db, "A3=10");
+        assert!(db.get(1002) == Ok(&CellData::IntData(20)));
+    }
+
+    #[test]
+    fn test_fill_down_series() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=10");
+        run_command(&mut db, "A2=20");
+        run_command(&mut db, "B1=5");
+        run_command(&mut db, "B2=3");
+
+        assert_eq!(fill_down(&mut db, (0, 0), (4, 1)), 0);
+        assert!(db.get(2) == Ok(&CellData::IntData(30)));
+        assert!(db.get(4) == Ok(&CellData::IntData(50)));
+        assert!(db.get(1004) == Ok(&CellData::IntData(-3)));
+    }
+
+    #[test]
+    fn test_fill_down_copies_single_constant() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=7");
+
+        assert_eq!(fill_down(&mut db, (0, 0), (3, 0)), 0);
+        assert!(db.get(3) == Ok(&CellData::IntData(7)));
+    }
+
+    #[test]
+    fn test_fill_down_over_old_values() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=1");
+        run_command(&mut db, "A2=2");
+        run_command(&mut db, "A3=99");
+        run_command(&mut db, "A4=4");
+
+        assert_eq!(fill_down(&mut db, (0, 0), (4, 0)), 0);
+        assert!(db.get(2) == Ok(&CellData::IntData(3)));
+        assert!(db.get(4) == Ok(&CellData::IntData(5)));
+
+        // A refilled series extends from its last seed, not from old values below it
+        run_command(&mut db, "A5=20");
+        assert_eq!(fill_down(&mut db, (0, 0), (5, 0)), 0);
+        assert!(db.get(4) == Ok(&CellData::IntData(5)));
+        assert!(db.get(5) == Ok(&CellData::IntData(6)));
+    }
+
+    #[test]
+    fn test_fill_down_overflow() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=2147483646");
+        run_command(&mut db, "A2=2147483647");
+
+        assert_eq!(fill_down(&mut db, (0, 0), (3, 0)), 2);
+        assert!(db.get(2) == Ok(&CellData::Empty));
+        assert!(db.get(3) == Ok(&CellData::Empty));
+    }
+
+    #[test]
+    fn test_fill_right() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=1");
+        run_command(&mut db, "B1=2");
+        run_command(&mut db, "A2=A1+1");
+
+        assert_eq!(fill_right(&mut db, (0, 0), (1, 3)), 0);
+        assert!(db.get(3000) == Ok(&CellData::IntData(4)));
+        assert!(db.get(3001) == Ok(&CellData::IntData(5)));
+
+        run_command(&mut db, "D1=100");
+        assert!(db.get(3001) == Ok(&CellData::IntData(101)));
+    }
+
+    #[test]
+    fn test_fill_series_detection() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=1");
+        run_command(&mut db, "A2=2");
+        run_command(&mut db, "A3=4");
+        // The series is given by the constants following the first difference
+        assert_eq!(
+            linear_series(&db, &[0, 1, 2, 3]),
+            Some((2, CellData::IntData(2), CellData::IntData(1)))
+        );
+        assert_eq!(linear_series(&db, &[2, 3]), None);
+
+        let _ = db.set_float(1000, 0.5);
+        let _ = db.set_float(1001, 1.0);
+        assert_eq!(
+            linear_series(&db, &[1000, 1001, 1002]),
+            Some((2, CellData::FloatData(1.0), CellData::FloatData(0.5)))
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::database::cell::CellData;
+use crate::database::Database;
+use crate::display::{column_index, generate_column_label};
+
+/// Comparison used by a filter predicate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl FilterOp {
+    /// Parses one of < <= > >= = == !=
+    pub fn parse(s: &str) -> Option<FilterOp> {
+        match s {
+            "<" => Some(FilterOp::Lt),
+            "<=" => Some(FilterOp::Le),
+            ">" => Some(FilterOp::Gt),
+            ">=" => Some(FilterOp::Ge),
+            "=" | "==" => Some(FilterOp::Eq),
+            "!=" => Some(FilterOp::Ne),
+            _ => None,
+        }
+    }
+
+    /// Checks if `a op b` holds
+    pub fn compare(&self, a: f32, b: f32) -> bool {
+        match self {
+            FilterOp::Lt => a < b,
+            FilterOp::Le => a <= b,
+            FilterOp::Gt => a > b,
+            FilterOp::Ge => a >= b,
+            FilterOp::Eq => a == b,
+            FilterOp::Ne => a != b,
+        }
+    }
+}
+
+impl std::fmt::Display for FilterOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            FilterOp::Lt => "<",
+            FilterOp::Le => "<=",
+            FilterOp::Gt => ">",
+            FilterOp::Ge => ">=",
+            FilterOp::Eq => "=",
+            FilterOp::Ne => "!=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// View filter hiding the rows of a table whose cell in column col does not satisfy `op value`
+/// Rows outside rows (inclusive) are never hidden
+/// The predicate is checked against the current data, so edits can show or hide rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    pub col: usize,
+    pub op: FilterOp,
+    pub value: f32,
+    pub rows: (usize, usize),
+}
+
+impl Filter {
+    /// Parses a predicate like "C > 100" or "B<=2.5" for the table spanning rows and cols
+    /// Returns an error message if the predicate is invalid
+    pub fn parse(
+        s: &str,
+        rows: (usize, usize),
+        cols: (usize, usize),
+    ) -> Result<Filter, &'static str> {
+        let s = s.trim();
+        let op_start = match s.find(['<', '>', '=', '!']) {
+            Some(idx) => idx,
+            None => return Err("Filter must look like :filter C > 100"),
+        };
+        let op_end = s[op_start..]
+            .find(|c| !['<', '>', '=', '!'].contains(&c))
+            .map_or(s.len(), |idx| op_start + idx);
+
+        let col = match column_index(s[..op_start].trim()) {
+            Some(col) => col as usize,
+            None => return Err("Invalid column in filter"),
+        };
+        if col < cols.0 || col > cols.1 {
+            return Err("Filter column is outside the table");
+        }
+
+        let op = match FilterOp::parse(&s[op_start..op_end]) {
+            Some(op) => op,
+            None => return Err("Filter operator must be one of < <= > >= = !="),
+        };
+
+        let value = match s[op_end..].trim().parse::<f32>() {
+            Ok(value) => value,
+            Err(_) => return Err("Filter value must be a number"),
+        };
+
+        Ok(Filter {
+            col,
+            op,
+            value,
+            rows,
+        })
+    }
+
+    /// Checks if row is shown, empty and error cells never match the predicate
+    pub fn matches(&self, db: &Database, row: usize) -> bool {
+        if row < self.rows.0 || row > self.rows.1 {
+            return true;
+        }
+
+        let data = match db.get((1000 * self.col + row) as u32) {
+            Ok(CellData::IntData(i)) => *i as f32,
+            Ok(CellData::FloatData(f)) => *f,
+            _ => return false,
+        };
+
+        self.op.compare(data, self.value)
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            generate_column_label(self.col as u32),
+            self.op,
+            self.value
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let f = Filter::parse("C > 100", (0, 9), (0, 5)).unwrap();
+        assert_eq!(
+            f,
+            Filter {
+                col: 2,
+                op: FilterOp::Gt,
+                value: 100.0,
+                rows: (0, 9)
+            }
+        );
+        assert_eq!(f.to_string(), "C > 100");
+
+        let f = Filter::parse("B<=2.5", (0, 9), (0, 5)).unwrap();
+        assert_eq!((f.col, f.op, f.value), (1, FilterOp::Le, 2.5));
+
+        assert!(Filter::parse("C 100", (0, 9), (0, 5)).is_err());
+        assert!(Filter::parse("G > 1", (0, 9), (0, 5)).is_err());
+        assert!(Filter::parse("C => 1", (0, 9), (0, 5)).is_err());
+        assert!(Filter::parse("C > x", (0, 9), (0, 5)).is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut db = Database::new(10, 10);
+        let _ = db.set_int(1000, 5);
+        let _ = db.set_float(1001, 1.5);
+        let _ = db.set_int(1005, 0);
+
+        let f = Filter::parse("B != 5", (1, 8), (0, 5)).unwrap();
+        // Row 0 is outside the table
+        assert!(f.matches(&db, 0));
+        assert!(f.matches(&db, 1));
+        // Empty cells never match
+        assert!(!f.matches(&db, 2));
+        assert!(f.matches(&db, 5));
+        assert!(f.matches(&db, 9));
+    }
+}
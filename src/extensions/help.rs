@@ -0,0 +1,122 @@
+use crate::extensions::complete::{COMMANDS, FUNCTIONS};
+use crate::extensions::keymap::{Keymap, KEY_MODES};
+
+/// Modes of the TUI, as shown in the status bar, with a description
+const MODES: [(&str, &str); 8] = [
+    ("NORMAL", "Move around the sheet, the app starts in it"),
+    (
+        "INSERT",
+        "Type a value or a formula like =A1+1 into the current cell",
+    ),
+    ("SELECT", "Extend a range from the cell where it started"),
+    ("GRAPH", "Chart of the selected range"),
+    ("STATS", "Statistics of the selected range"),
+    ("HELP", "This screen"),
+    (
+        "MESSAGES",
+        "Recent errors and saves, scrolled like this screen",
+    ),
+    (
+        "RECOVER",
+        "Press y to recover the unsaved changes of a crashed session, n to drop them",
+    ),
+];
+
+/// Keys of the editing line, shared by Insert mode and the ':' command line
+const EDITING_KEYS: [(&str, &str); 7] = [
+    ("Left, Right", "Move the cursor"),
+    ("C-Left, C-Right", "Move the cursor by word"),
+    ("Home, End", "Go to the start or the end of the line"),
+    ("Backspace, Delete", "Delete before or after the cursor"),
+    ("Up, Down", "Previous or next line of the history"),
+    ("Tab", "Complete commands, file names, themes and functions"),
+    ("Enter, Esc", "Run or cancel the line"),
+];
+
+/// Lines of the help screen, the keys are the ones of keymap so rebound keys are shown
+pub fn help_lines(keymap: &Keymap) -> Vec<String> {
+    let entry = |name: &str, description: &str| format!("  {:<24}{}", name, description);
+
+    let mut lines = vec!["Modes".to_string()];
+    lines.extend(
+        MODES
+            .iter()
+            .map(|(mode, description)| entry(mode, description)),
+    );
+
+    for mode in KEY_MODES {
+        lines.push(String::new());
+        lines.push(format!("Keys in {} mode", mode.name()));
+        for (action, keys) in keymap.bindings(mode) {
+            if keys.is_empty() {
+                continue;
+            }
+            let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+            lines.push(entry(&keys.join(", "), action.description()));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push("Keys in insert mode and on the command line".to_string());
+    lines.extend(
+        EDITING_KEYS
+            .iter()
+            .map(|(keys, description)| entry(keys, description)),
+    );
+
+    lines.push(String::new());
+    lines.push("Commands, entered after ':'".to_string());
+    lines.extend(
+        COMMANDS
+            .iter()
+            .map(|(_, example, description)| entry(example, description)),
+    );
+    lines.push(entry(
+        ":s/old/new/",
+        "Replace text in the formulas of the selection",
+    ));
+
+    lines.push(String::new());
+    lines.push("Functions".to_string());
+    lines.extend(
+        FUNCTIONS
+            .iter()
+            .map(|(_, example, description)| entry(example, description)),
+    );
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::keymap::{Action, Key, KeyMode};
+
+    #[test]
+    fn test_help_lines() {
+        let mut keymap = Keymap::default();
+        let lines = help_lines(&keymap);
+        assert!(lines.contains(&"Keys in select mode".to_string()));
+        assert!(lines.contains(&format!("  {:<24}{}", "g", "Draw a chart of the selection")));
+        assert!(lines.contains(&format!(
+            "  {:<24}{}",
+            ":o data.csv", "Open a .csv or .sheet file"
+        )));
+        assert!(lines.iter().any(|line| line.starts_with("  =SUM(A1:B5)")));
+
+        // Rebound keys are listed instead of the defaults
+        keymap.bind(
+            KeyMode::Normal,
+            Action::MoveLeft,
+            vec![Key::parse("h").unwrap(), Key::parse("Left").unwrap()],
+        );
+        let lines = help_lines(&keymap);
+        assert!(lines.contains(&format!("  {:<24}{}", "h, Left", "Move left")));
+        // Select mode keeps its keys
+        let default_left = format!("  {:<24}{}", "a, Left", "Move left");
+        assert_eq!(
+            lines.iter().filter(|line| **line == default_left).count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Most entries loaded back from the history file
+const MAX_ENTRIES: usize = 500;
+
+/// List of previously entered lines, browsed with Up/Down
+/// pos is the entry being shown, entries.len() when back at the line being typed (kept in draft)
+/// New entries are appended to file, if any
+pub struct History {
+    entries: Vec<String>,
+    pos: usize,
+    draft: String,
+    file: Option<PathBuf>,
+}
+
+impl History {
+    /// Creates an empty history that is not saved
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            pos: 0,
+            draft: String::new(),
+            file: None,
+        }
+    }
+
+    /// Loads the lines of file accepted by keep, new entries are appended to it
+    /// A missing or unreadable file gives an empty history
+    pub fn load(file: PathBuf, keep: fn(&str) -> bool) -> Self {
+        let mut entries: Vec<String> = match File::open(&file) {
+            Ok(f) => BufReader::new(f)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|line| keep(line))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        if entries.len() > MAX_ENTRIES {
+            entries.drain(..entries.len() - MAX_ENTRIES);
+        }
+
+        Self {
+            pos: entries.len(),
+            entries,
+            draft: String::new(),
+            file: Some(file),
+        }
+    }
+
+    /// Adds line as the newest entry, unless it is empty or repeats the newest entry
+    /// Failing to write the history file is not an error
+    pub fn push(&mut self, line: &str) {
+        if !line.trim().is_empty() && self.entries.last().map(String::as_str) != Some(line) {
+            self.entries.push(line.to_string());
+
+            if let Some(file) = &self.file {
+                if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(file) {
+                    let _ = writeln!(f, "{}", line);
+                }
+            }
+        }
+        self.reset();
+    }
+
+    /// Goes back to the line being typed
+    pub fn reset(&mut self) {
+        self.pos = self.entries.len();
+        self.draft.clear();
+    }
+
+    /// Returns the entry before the one shown, current being the line being typed
+    pub fn prev(&mut self, current: &str) -> Option<&str> {
+        if self.pos == 0 {
+            return None;
+        }
+        if self.pos == self.entries.len() {
+            self.draft = current.to_string();
+        }
+        self.pos -= 1;
+        Some(&self.entries[self.pos])
+    }
+
+    /// Returns the entry after the one shown, or the line that was being typed
+    pub fn next(&mut self) -> Option<&str> {
+        if self.pos >= self.entries.len() {
+            return None;
+        }
+        self.pos += 1;
+        if self.pos == self.entries.len() {
+            Some(&self.draft)
+        } else {
+            Some(&self.entries[self.pos])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse() {
+        let mut history = History::new();
+        assert_eq!(history.prev("x"), None);
+
+        history.push(":w a.csv");
+        history.push(":w a.csv");
+        history.push("");
+        history.push(":q");
+
+        assert_eq!(history.prev(":g"), Some(":q"));
+        assert_eq!(history.prev(":q"), Some(":w a.csv"));
+        assert_eq!(history.prev(":w a.csv"), None);
+        assert_eq!(history.next(), Some(":q"));
+        assert_eq!(history.next(), Some(":g"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn test_file() {
+        let file = std::env::temp_dir().join(format!("history_test_{}", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+
+        let mut history = History::load(file.clone(), |line| line.starts_with(':'));
+        history.push(":o data.csv");
+        let mut formulas = History::load(file.clone(), |line| !line.starts_with(':'));
+        formulas.push("=SUM(A1:A3)");
+
+        let mut history = History::load(file.clone(), |line| line.starts_with(':'));
+        assert_eq!(history.prev(""), Some(":o data.csv"));
+        assert_eq!(history.prev(""), None);
+
+        let mut formulas = History::load(file.clone(), |line| !line.starts_with(':'));
+        assert_eq!(formulas.prev(""), Some("=SUM(A1:A3)"));
+
+        let _ = std::fs::remove_file(&file);
+    }
+}
@@ -1,117 +1,91 @@
-use crate::display::generate_column_label;
 use crate::extensions::app::Mode;
 use crate::extensions::app::{App, AppCommand};
-use crate::extensions::command_handler::handle;
-use crate::{evaluator::evaluator, parser::parse};
-use crossterm::event::{self, Event, KeyCode};
+use crate::extensions::command_handler::{handle, recover};
+use crate::extensions::help::help_lines;
+use crate::extensions::keymap::{Action, KeyMode};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use std::time::Instant;
 
 pub fn handle_input(app: &mut App) -> std::io::Result<Option<AppCommand>> {
-    if let Event::Key(key_event) = event::read()? {
+    let event = event::read()?;
+    if let Event::Mouse(mouse_event) = event {
+        handle_mouse(app, mouse_event);
+        return Ok(None);
+    }
+
+    if let Event::Key(key_event) = event {
+        if key_event.code != KeyCode::Tab {
+            app.completion = None;
+        }
+        app.error = None;
         match app.mode {
-            Mode::Normal => match key_event.code {
-                KeyCode::Char('i') => {
+            Mode::Normal => match app.keymap.action(KeyMode::Normal, key_event) {
+                Some(Action::Edit) => app.edit(app.selected),
+                Some(Action::Change) => {
                     app.mode = Mode::Insert;
-                    app.input_buffer.clear();
+                    app.set_input("");
                 }
-                KeyCode::Char('v') => {
+                Some(Action::Select) => {
                     app.mode = Mode::Select(app.selected.0, app.selected.1);
                     app.input_buffer.clear();
                 }
-                KeyCode::Left | KeyCode::Char('a') => app.move_left(),
-                KeyCode::Right | KeyCode::Char('d') => app.move_right(),
-                KeyCode::Up | KeyCode::Char('w') => app.move_up(),
-                KeyCode::Down | KeyCode::Char('s') => app.move_down(),
-                KeyCode::Char(':') => {
+                Some(Action::Yank) => app.yank(),
+                Some(Action::Cut) => app.cut(),
+                Some(Action::Clear) => app.clear(),
+                Some(Action::Paste) => {
+                    let ec = app.paste();
+                    if ec != 0 {
+                        app.mode = Mode::ErrMsg(crate::ERRMSG[ec as usize]);
+                    }
+                }
+                Some(Action::LastRow) => app.jump_last_row(),
+                Some(Action::RowStart) => app.jump_to(app.selected.0, 0),
+                Some(Action::RowEnd) => app.jump_to(app.selected.0, app.db.num_cols as usize - 1),
+                Some(Action::Goto) => {
+                    // Opens the goto prompt, a second press jumps to the first row instead
                     app.mode = Mode::NormalCommand;
+                    app.set_input(":goto ");
+                }
+                Some(Action::Command) => {
+                    app.mode = Mode::NormalCommand;
+                    app.set_input(":");
+                }
+                Some(Action::Search) => {
+                    app.mode = Mode::NormalCommand;
+                    app.set_input("/");
+                }
+                Some(Action::Help) => {
+                    app.mode = Mode::Help(0);
                     app.input_buffer.clear();
-                    app.input_buffer.push(':');
                 }
-                _ => {}
+                Some(action @ (Action::SearchNext | Action::SearchPrev)) => {
+                    let found = app.search_next(action == Action::SearchNext);
+                    if !found && app.search.is_some() {
+                        app.mode = Mode::ErrMsg("Pattern not found");
+                    }
+                }
+                Some(action) => {
+                    move_action(app, action);
+                }
+                None => {}
             },
             Mode::Insert => match key_event.code {
                 KeyCode::Esc => {
                     app.mode = Mode::Normal;
                     app.input_buffer.clear();
                 }
-                KeyCode::Backspace => {
-                    app.input_buffer.pop();
-                }
-                KeyCode::Char(c) => {
-                    app.input_buffer.push(c);
-                }
-                KeyCode::Enter => {
-                    if app.input_buffer.len() == 0 {
+                _ if edit_line(app, key_event) => {}
+                KeyCode::Enter if !app.input_buffer.is_empty() => {
+                    app.formula_history.push(&app.input_buffer);
+                    let ec = app.commit_input();
+                    if ec != 0 {
+                        // The line stays in Insert mode so it can be fixed
+                        app.show_error(crate::ERRMSG[ec as usize]);
                     } else {
-                        let mut cell = generate_column_label(app.selected.1 as u32);
-                        cell += &((app.selected.0 + 1).to_string());
-
-                        let ip = app.input_buffer.clone();
-                        // Try i32
-                        let ip_int = ip.parse::<i32>();
-                        match ip_int {
-                            Ok(_) => {
-                                cell += "=";
-                                cell += &ip;
-
-                                let (mut tmp1, mut tmp2, mut tmp3): (u32, bool, bool) =
-                                    (0, false, false);
-                                let r = parse(&cell);
-                                let ec = evaluator(r, &mut app.db, &mut tmp1, &mut tmp2, &mut tmp3);
-
-                                if ec != 0 {
-                                    app.mode = Mode::ErrMsg(crate::ERRMSG[ec as usize]);
-                                    app.input_buffer.clear();
-                                    return Ok(None);
-                                }
-
-                                app.input_buffer.clear();
-                                app.mode = Mode::Normal;
-                                return Ok(None);
-                            }
-                            Err(_) => {}
-                        }
-
-                        // Try f32
-                        let ip_fl = ip.parse::<f32>();
-                        match ip_fl {
-                            Ok(_) => {
-                                app.mode = Mode::ErrMsg("FLOATS UNDER PROGRESS");
-                                app.input_buffer.clear();
-                                return Ok(None);
-                                // Implement floats in parser
-                                // cell += "=";
-                                // cell += &ip;
-
-                                // let (mut tmp1, mut tmp2, mut tmp3): (u32, bool, bool) = (0, false, false);
-                                // let r = parse(&cell);
-                                // let _ = evaluator(r, &mut app.db, &mut tmp1, &mut tmp2, &mut tmp3);
-
-                                // let _ = app
-                                //     .db
-                                //     .set_float((1000 * app.selected.1 + app.selected.0) as u32, f);
-
-                                // app.input_buffer.clear();
-                                // app.mode = Mode::Normal;
-                                // return Ok(None);
-                            }
-                            Err(_) => {}
-                        }
-
-                        cell += &ip;
-
-                        let (mut tmp1, mut tmp2, mut tmp3): (u32, bool, bool) = (0, false, false);
-                        let r = parse(&cell);
-                        let ec = evaluator(r, &mut app.db, &mut tmp1, &mut tmp2, &mut tmp3);
-
-                        if ec != 0 {
-                            app.mode = Mode::ErrMsg(crate::ERRMSG[ec as usize]);
-                            app.input_buffer.clear();
-                            return Ok(None);
-                        }
-
                         app.input_buffer.clear();
                         app.mode = Mode::Normal;
-                        return Ok(None);
                     }
                 }
                 _ => {}
@@ -119,39 +93,110 @@ pub fn handle_input(app: &mut App) -> std::io::Result<Option<AppCommand>> {
             Mode::NormalCommand => match key_event.code {
                 KeyCode::Esc => {
                     app.input_buffer.clear();
+                    app.cmd_selection = None;
                     app.mode = Mode::Normal;
                 }
-                KeyCode::Backspace => {
-                    app.input_buffer.pop();
+                KeyCode::Backspace if app.input_buffer.len() <= 1 => {
+                    // Deleting the ':' or '/' prompt leaves the command line
+                    app.input_buffer.clear();
+                    app.cmd_selection = None;
+                    app.mode = Mode::Normal;
                 }
-                KeyCode::Char(c) => {
-                    app.input_buffer.push(c);
+                // The prompt itself cannot be deleted while a command follows it
+                KeyCode::Backspace if app.cursor_pos() <= 1 => {}
+                _ if app.input_buffer == ":goto "
+                    && app.keymap.action(KeyMode::Normal, key_event) == Some(Action::Goto) =>
+                {
+                    app.input_buffer.clear();
+                    app.mode = Mode::Normal;
+                    app.jump_first_row();
+                }
+                _ if edit_line(app, key_event) => {
+                    // The cursor never moves before the prompt
+                    app.cursor = app.cursor_pos().max(1);
+                }
+                KeyCode::Enter if app.input_buffer.starts_with('/') => {
+                    app.history.push(&app.input_buffer);
+                    app.search = Some(app.input_buffer[1..].to_string());
+                    app.input_buffer.clear();
+                    app.mode = if app.search_next(true) {
+                        Mode::Normal
+                    } else {
+                        Mode::ErrMsg("Pattern not found")
+                    };
                 }
-                KeyCode::Enter => match handle(app, app.input_buffer[1..].to_string().clone()) {
-                    Ok(Some(val)) => {
-                        return Ok(Some(val));
+                KeyCode::Enter => {
+                    app.history.push(&app.input_buffer);
+                    let res = handle(app, app.input_buffer[1..].to_string().clone());
+                    if let Mode::ErrMsg(msg) = app.mode {
+                        if !app.input_buffer.is_empty() {
+                            // A failed command stays on the command line so it can be fixed
+                            app.mode = Mode::NormalCommand;
+                            app.show_error(msg);
+                        }
+                    }
+                    if app.mode != Mode::NormalCommand {
+                        app.cmd_selection = None;
                     }
-                    Ok(None) => {}
-                    Err(err) => {
-                        return Err(err);
+                    match res {
+                        Ok(Some(val)) => {
+                            return Ok(Some(val));
+                        }
+                        Ok(None) => {}
+                        Err(err) => {
+                            return Err(err);
+                        }
                     }
-                },
+                }
                 _ => {}
             },
-            Mode::Select(r, c) => match key_event.code {
-                KeyCode::Esc => {
+            Mode::Select(r, c) => match app.keymap.action(KeyMode::Select, key_event) {
+                Some(Action::Exit) => {
                     app.mode = Mode::Normal;
                     app.input_buffer.clear();
                 }
-                KeyCode::Left | KeyCode::Char('a') => app.move_left(),
-                KeyCode::Right | KeyCode::Char('d') => app.move_right(),
-                KeyCode::Up | KeyCode::Char('w') => app.move_up(),
-                KeyCode::Down | KeyCode::Char('s') => app.move_down(),
-                KeyCode::Char('g') => {
+                Some(Action::Graph) => {
                     app.mode = Mode::Graph((r, c), (app.selected.0, app.selected.1));
                     app.input_buffer.clear();
                 }
-                _ => {}
+                Some(Action::Stats) => {
+                    app.mode = Mode::Stats((r, c), (app.selected.0, app.selected.1));
+                    app.input_buffer.clear();
+                }
+                Some(Action::Command) => {
+                    // Commands run from Select mode act on the selection
+                    app.cmd_selection = app.selection();
+                    app.mode = Mode::NormalCommand;
+                    app.set_input(":");
+                }
+                Some(Action::Yank) => {
+                    app.yank();
+                    app.mode = Mode::Normal;
+                }
+                Some(Action::Cut) => {
+                    app.cut();
+                    app.mode = Mode::Normal;
+                }
+                Some(Action::Clear) => {
+                    app.clear();
+                    app.mode = Mode::Normal;
+                }
+                Some(action @ (Action::FillDown | Action::FillRight | Action::Paste)) => {
+                    let ec = match action {
+                        Action::FillDown => app.fill_down(),
+                        Action::FillRight => app.fill_right(),
+                        _ => app.paste(),
+                    };
+                    app.mode = if ec != 0 {
+                        Mode::ErrMsg(crate::ERRMSG[ec as usize])
+                    } else {
+                        Mode::Normal
+                    };
+                }
+                Some(action) => {
+                    move_action(app, action);
+                }
+                None => {}
             },
             Mode::ErrMsg(_) => match key_event.kind {
                 event::KeyEventKind::Press => {
@@ -160,14 +205,142 @@ pub fn handle_input(app: &mut App) -> std::io::Result<Option<AppCommand>> {
                 }
                 _ => {}
             },
-            Mode::Graph(a, _) => match key_event.code {
-                KeyCode::Esc | KeyCode::Char('q') => {
+            Mode::Graph(a, _) => match app.keymap.action(KeyMode::Graph, key_event) {
+                Some(Action::Exit) => {
                     app.mode = Mode::Select(a.0, a.1);
                     app.input_buffer.clear();
                 }
+                Some(Action::ChartType) => app.chart = app.chart.next(),
+                Some(Action::Command) => {
+                    // Commands run from Graph mode act on the charted range
+                    app.cmd_selection = app.selection();
+                    app.mode = Mode::NormalCommand;
+                    app.set_input(":");
+                }
                 _ => {}
             },
+            Mode::Help(scroll) => {
+                let lines = help_lines(&app.keymap).len();
+                if let Some(scroll) = scroll_text(app, key_event, scroll, lines) {
+                    app.mode = Mode::Help(scroll);
+                }
+            }
+            Mode::Recover => match key_event.code {
+                KeyCode::Char('y') => recover(app, true),
+                KeyCode::Char('n') => recover(app, false),
+                _ => {}
+            },
+            Mode::Messages(scroll) => {
+                if let Some(scroll) = scroll_text(app, key_event, scroll, app.messages.len()) {
+                    app.mode = Mode::Messages(scroll);
+                }
+            }
+            Mode::Stats(a, _) => {
+                if app.keymap.action(KeyMode::Stats, key_event) == Some(Action::Exit) {
+                    app.mode = Mode::Select(a.0, a.1);
+                    app.input_buffer.clear();
+                }
+            }
         }
     }
     Ok(None)
 }
+
+/// Handles the keys of the help screen, also used by the message log
+/// Returns the new scroll of a text of the given number of lines, None if it was closed
+fn scroll_text(app: &mut App, key_event: KeyEvent, scroll: u16, lines: usize) -> Option<u16> {
+    let last_line = lines.saturating_sub(1) as u16;
+    let scroll = match app.keymap.action(KeyMode::Help, key_event) {
+        Some(Action::Exit) => {
+            app.mode = Mode::Normal;
+            return None;
+        }
+        Some(Action::MoveUp) => scroll.saturating_sub(1),
+        Some(Action::MoveDown) => scroll + 1,
+        Some(Action::PageUp) => scroll.saturating_sub(HELP_PAGE),
+        Some(Action::PageDown) => scroll + HELP_PAGE,
+        _ => scroll,
+    };
+    Some(scroll.min(last_line))
+}
+
+/// Does the actions moving around the sheet or resizing columns, shared by Normal and Select modes
+fn move_action(app: &mut App, action: Action) {
+    match action {
+        Action::MoveLeft => app.move_left(),
+        Action::MoveRight => app.move_right(),
+        Action::MoveUp => app.move_up(),
+        Action::MoveDown => app.move_down(),
+        Action::PageDown => app.page_down(),
+        Action::PageUp => app.page_up(),
+        Action::JumpLeft => app.jump_edge(0, -1),
+        Action::JumpRight => app.jump_edge(0, 1),
+        Action::JumpUp => app.jump_edge(-1, 0),
+        Action::JumpDown => app.jump_edge(1, 0),
+        Action::Widen => app.resize_columns(1),
+        Action::Narrow => app.resize_columns(-1),
+        _ => {}
+    }
+}
+
+/// Lines scrolled by a page of the help screen
+const HELP_PAGE: u16 = 10;
+
+/// Rows scrolled by one step of the scroll wheel
+const SCROLL_ROWS: i32 = 3;
+
+/// Handles the mouse in Normal and Select modes
+/// A click selects a cell, a double click edits it, dragging selects a range and the wheel scrolls
+fn handle_mouse(app: &mut App, mouse_event: MouseEvent) {
+    if !matches!(app.mode, Mode::Normal | Mode::Select(_, _)) {
+        return;
+    }
+    let cell = app.cell_at(mouse_event.column, mouse_event.row);
+
+    match mouse_event.kind {
+        MouseEventKind::ScrollDown => app.scroll(SCROLL_ROWS),
+        MouseEventKind::ScrollUp => app.scroll(-SCROLL_ROWS),
+        MouseEventKind::Down(MouseButton::Left) => {
+            if let Some(cell) = cell {
+                app.mode = Mode::Normal;
+                app.input_buffer.clear();
+                if app.click(cell, Instant::now()) {
+                    // Edits the cell like i does
+                    app.edit(cell);
+                }
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            if let Some(cell) = cell {
+                // The range starts at the cell where the button was pressed
+                if app.mode == Mode::Normal && cell != app.selected {
+                    app.mode = Mode::Select(app.selected.0, app.selected.1);
+                }
+                app.selected = cell;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Handles the line editing, history and completion keys shared by Insert mode and the command line
+/// Returns false if key_event is not an editing key
+fn edit_line(app: &mut App, key_event: KeyEvent) -> bool {
+    let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    match key_event.code {
+        KeyCode::Left if ctrl => app.word_left(),
+        KeyCode::Right if ctrl => app.word_right(),
+        KeyCode::Left => app.cursor_left(),
+        KeyCode::Right => app.cursor_right(),
+        KeyCode::Home => app.cursor_home(),
+        KeyCode::End => app.cursor_end(),
+        KeyCode::Backspace => app.delete_back(),
+        KeyCode::Delete => app.delete_forward(),
+        KeyCode::Up => app.history_prev(),
+        KeyCode::Down => app.history_next(),
+        KeyCode::Tab => app.complete(),
+        KeyCode::Char(c) if !ctrl => app.insert_char(c),
+        _ => return false,
+    }
+    true
+}
@@ -0,0 +1,479 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A key, with or without Ctrl, written like "a", "Left" or "C-d"
+/// Shift is not part of the key, it is in the character ('G' is shift-g)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+/// Names of the keys that are not a single character
+const KEY_NAMES: [(&str, KeyCode); 13] = [
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Delete", KeyCode::Delete),
+    ("Backspace", KeyCode::Backspace),
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Space", KeyCode::Char(' ')),
+];
+
+impl Key {
+    /// Parses a key like "a", "$", "Left", "Esc" or "C-d", None if it is not a key
+    pub fn parse(s: &str) -> Option<Key> {
+        let (ctrl, name) = match s.strip_prefix("C-") {
+            Some(name) if !name.is_empty() => (true, name),
+            _ => (false, s),
+        };
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => KEY_NAMES
+                .iter()
+                .find(|(key_name, _)| *key_name == name)
+                .map(|&(_, code)| code)?,
+        };
+        Some(Key { code, ctrl })
+    }
+
+    pub fn from_event(key_event: KeyEvent) -> Key {
+        Key {
+            code: key_event.code,
+            ctrl: key_event.modifiers.contains(KeyModifiers::CONTROL),
+        }
+    }
+}
+
+/// Writes the key in the syntax read by parse
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "C-")?;
+        }
+        match KEY_NAMES.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{}", c),
+                code => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// Modes whose keys can be bound, the editing modes take text instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMode {
+    Normal,
+    Select,
+    Graph,
+    Stats,
+    Help,
+}
+
+/// Every mode whose keys can be bound, in the order of the help screen
+pub const KEY_MODES: [KeyMode; 5] = [
+    KeyMode::Normal,
+    KeyMode::Select,
+    KeyMode::Graph,
+    KeyMode::Stats,
+    KeyMode::Help,
+];
+
+impl KeyMode {
+    /// Name of the mode in the config file, e.g. [keys.normal]
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyMode::Normal => "normal",
+            KeyMode::Select => "select",
+            KeyMode::Graph => "graph",
+            KeyMode::Stats => "stats",
+            KeyMode::Help => "help",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyMode> {
+        KEY_MODES.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// What a key does, see ACTIONS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    PageDown,
+    PageUp,
+    JumpLeft,
+    JumpRight,
+    JumpUp,
+    JumpDown,
+    LastRow,
+    RowStart,
+    RowEnd,
+    Goto,
+    Edit,
+    Change,
+    Select,
+    Yank,
+    Cut,
+    Paste,
+    Clear,
+    FillDown,
+    FillRight,
+    Widen,
+    Narrow,
+    Command,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Graph,
+    Stats,
+    ChartType,
+    Help,
+    Exit,
+}
+
+/// Name of every action in the config file, with a description
+pub const ACTIONS: [(Action, &str, &str); 34] = [
+    (Action::MoveLeft, "move_left", "Move left"),
+    (Action::MoveRight, "move_right", "Move right"),
+    (Action::MoveUp, "move_up", "Move up"),
+    (Action::MoveDown, "move_down", "Move down"),
+    (Action::PageDown, "page_down", "Move half a page down"),
+    (Action::PageUp, "page_up", "Move half a page up"),
+    (
+        Action::JumpLeft,
+        "jump_left",
+        "Jump to the left edge of the data",
+    ),
+    (
+        Action::JumpRight,
+        "jump_right",
+        "Jump to the right edge of the data",
+    ),
+    (
+        Action::JumpUp,
+        "jump_up",
+        "Jump to the top edge of the data",
+    ),
+    (
+        Action::JumpDown,
+        "jump_down",
+        "Jump to the bottom edge of the data",
+    ),
+    (Action::LastRow, "last_row", "Go to the last row"),
+    (Action::RowStart, "row_start", "Go to the first column"),
+    (Action::RowEnd, "row_end", "Go to the last column"),
+    (
+        Action::Goto,
+        "goto",
+        "Go to a cell, twice for the first row",
+    ),
+    (
+        Action::Edit,
+        "edit",
+        "Edit the cell, starting from its formula",
+    ),
+    (
+        Action::Change,
+        "change",
+        "Edit the cell, starting from an empty line",
+    ),
+    (Action::Select, "select", "Start selecting a range"),
+    (Action::Yank, "yank", "Copy the cells"),
+    (Action::Cut, "cut", "Cut the cells"),
+    (Action::Paste, "paste", "Paste copied cells"),
+    (Action::Clear, "clear", "Clear the cells"),
+    (
+        Action::FillDown,
+        "fill_down",
+        "Fill the first row down the selection",
+    ),
+    (
+        Action::FillRight,
+        "fill_right",
+        "Fill the first column right across the selection",
+    ),
+    (Action::Widen, "widen", "Widen the columns"),
+    (Action::Narrow, "narrow", "Narrow the columns"),
+    (Action::Command, "command", "Enter a command"),
+    (Action::Search, "search", "Search the cells"),
+    (Action::SearchNext, "search_next", "Go to the next match"),
+    (
+        Action::SearchPrev,
+        "search_prev",
+        "Go to the previous match",
+    ),
+    (Action::Graph, "graph", "Draw a chart of the selection"),
+    (Action::Stats, "stats", "Show statistics of the selection"),
+    (Action::ChartType, "chart_type", "Change the chart type"),
+    (Action::Help, "help", "Show this help"),
+    (Action::Exit, "exit", "Leave the mode"),
+];
+
+impl Action {
+    pub fn description(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(action, _, _)| *action == self)
+            .map_or("", |(_, _, description)| description)
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+    }
+}
+
+/// Keys moving around the sheet, the same in Normal and Select modes
+const MOVES: [(Action, &[&str]); 10] = [
+    (Action::MoveLeft, &["a", "Left"]),
+    (Action::MoveRight, &["d", "Right"]),
+    (Action::MoveUp, &["w", "Up"]),
+    (Action::MoveDown, &["s", "Down"]),
+    (Action::PageDown, &["C-d"]),
+    (Action::PageUp, &["C-u"]),
+    (Action::JumpLeft, &["C-Left"]),
+    (Action::JumpRight, &["C-Right"]),
+    (Action::JumpUp, &["C-Up"]),
+    (Action::JumpDown, &["C-Down"]),
+];
+
+const NORMAL_KEYS: [(Action, &[&str]); 18] = [
+    (Action::Edit, &["i"]),
+    (Action::Change, &["c"]),
+    (Action::Select, &["v"]),
+    (Action::Yank, &["y"]),
+    (Action::Cut, &["x"]),
+    (Action::Paste, &["p"]),
+    // Not dd like vim, d moves right
+    (Action::Clear, &["Delete"]),
+    (Action::Widen, &[">"]),
+    (Action::Narrow, &["<"]),
+    (Action::LastRow, &["G"]),
+    (Action::RowStart, &["0"]),
+    (Action::RowEnd, &["$"]),
+    (Action::Goto, &["g"]),
+    (Action::Command, &[":"]),
+    (Action::Search, &["/"]),
+    (Action::SearchNext, &["n"]),
+    (Action::SearchPrev, &["N"]),
+    (Action::Help, &["?"]),
+];
+
+const SELECT_KEYS: [(Action, &[&str]); 12] = [
+    (Action::Exit, &["Esc"]),
+    (Action::Widen, &[">"]),
+    (Action::Narrow, &["<"]),
+    (Action::Graph, &["g"]),
+    (Action::Stats, &["S"]),
+    (Action::Command, &[":"]),
+    (Action::Yank, &["y"]),
+    (Action::Cut, &["x"]),
+    (Action::Paste, &["p"]),
+    (Action::Clear, &["Delete"]),
+    (Action::FillDown, &["D"]),
+    (Action::FillRight, &["R"]),
+];
+
+const GRAPH_KEYS: [(Action, &[&str]); 3] = [
+    (Action::Exit, &["Esc", "q"]),
+    (Action::ChartType, &["t"]),
+    (Action::Command, &[":"]),
+];
+
+const STATS_KEYS: [(Action, &[&str]); 1] = [(Action::Exit, &["Esc", "q"])];
+
+const HELP_KEYS: [(Action, &[&str]); 5] = [
+    (Action::Exit, &["Esc", "q", "?"]),
+    (Action::MoveUp, &["Up", "w", "k"]),
+    (Action::MoveDown, &["Down", "s", "j"]),
+    (Action::PageUp, &["PageUp", "C-u"]),
+    (Action::PageDown, &["PageDown", "C-d"]),
+];
+
+/// Keys of every action, per mode
+/// An action may have several keys, a key does at most one action per mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(KeyMode, Action, Vec<Key>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keys = |names: &[&str]| names.iter().filter_map(|name| Key::parse(name)).collect();
+        let mut bindings = Vec::new();
+        for (mode, table) in [
+            (KeyMode::Normal, &MOVES[..]),
+            (KeyMode::Normal, &NORMAL_KEYS[..]),
+            (KeyMode::Select, &MOVES[..]),
+            (KeyMode::Select, &SELECT_KEYS[..]),
+            (KeyMode::Graph, &GRAPH_KEYS[..]),
+            (KeyMode::Stats, &STATS_KEYS[..]),
+            (KeyMode::Help, &HELP_KEYS[..]),
+        ] {
+            for &(action, names) in table {
+                bindings.push((mode, action, keys(names)));
+            }
+        }
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// Action of the key pressed in mode, if any
+    pub fn action(&self, mode: KeyMode, key_event: KeyEvent) -> Option<Action> {
+        let key = Key::from_event(key_event);
+        self.bindings
+            .iter()
+            .find(|(m, _, keys)| *m == mode && keys.contains(&key))
+            .map(|(_, action, _)| *action)
+    }
+
+    /// Actions of mode with their keys, in the order of the binding table
+    pub fn bindings(&self, mode: KeyMode) -> impl Iterator<Item = (Action, &[Key])> {
+        self.bindings
+            .iter()
+            .filter(move |(m, _, _)| *m == mode)
+            .map(|(_, action, keys)| (*action, keys.as_slice()))
+    }
+
+    /// Binds keys to action in mode, replacing its previous keys
+    /// The keys stop doing other actions of mode
+    /// Returns false if action cannot be done in mode
+    pub fn bind(&mut self, mode: KeyMode, action: Action, keys: Vec<Key>) -> bool {
+        if !self
+            .bindings
+            .iter()
+            .any(|(m, a, _)| *m == mode && *a == action)
+        {
+            return false;
+        }
+        for (m, a, old_keys) in self.bindings.iter_mut() {
+            if *m != mode {
+                continue;
+            }
+            if *a == action {
+                *old_keys = keys.clone();
+            } else {
+                old_keys.retain(|key| !keys.contains(key));
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_key() {
+        for name in ["a", "$", "G", "Left", "Esc", "C-d", "C-Down", "Space"] {
+            assert_eq!(Key::parse(name).unwrap().to_string(), name);
+        }
+        assert_eq!(
+            Key::parse("C-u"),
+            Some(Key {
+                code: KeyCode::Char('u'),
+                ctrl: true
+            })
+        );
+        assert!(Key::parse("").is_none());
+        assert!(Key::parse("Lft").is_none());
+        assert!(Key::parse("C-").is_none());
+    }
+
+    #[test]
+    fn test_default_keys() {
+        let keymap = Keymap::default();
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            keymap.action(KeyMode::Normal, event(KeyCode::Char('d'), none)),
+            Some(Action::MoveRight)
+        );
+        assert_eq!(
+            keymap.action(
+                KeyMode::Normal,
+                event(KeyCode::Char('d'), KeyModifiers::CONTROL)
+            ),
+            Some(Action::PageDown)
+        );
+        assert_eq!(
+            keymap.action(
+                KeyMode::Normal,
+                event(KeyCode::Char('G'), KeyModifiers::SHIFT)
+            ),
+            Some(Action::LastRow)
+        );
+        assert_eq!(
+            keymap.action(KeyMode::Select, event(KeyCode::Char('g'), none)),
+            Some(Action::Graph)
+        );
+        assert_eq!(
+            keymap.action(KeyMode::Graph, event(KeyCode::Char('q'), none)),
+            Some(Action::Exit)
+        );
+        assert_eq!(
+            keymap.action(KeyMode::Stats, event(KeyCode::Char('t'), none)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut keymap = Keymap::default();
+        let keys = |names: &[&str]| names.iter().map(|n| Key::parse(n).unwrap()).collect();
+
+        assert!(keymap.bind(KeyMode::Normal, Action::MoveLeft, keys(&["h", "Left"])));
+        assert!(keymap.bind(KeyMode::Normal, Action::MoveDown, keys(&["j", "Down"])));
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            keymap.action(KeyMode::Normal, event(KeyCode::Char('h'), none)),
+            Some(Action::MoveLeft)
+        );
+        assert_eq!(
+            keymap.action(KeyMode::Normal, event(KeyCode::Char('a'), none)),
+            None
+        );
+        // Select mode keeps its keys
+        assert_eq!(
+            keymap.action(KeyMode::Select, event(KeyCode::Char('a'), none)),
+            Some(Action::MoveLeft)
+        );
+
+        // A key taken from another action stops doing it
+        assert!(keymap.bind(KeyMode::Normal, Action::Cut, keys(&["d"])));
+        assert_eq!(
+            keymap.action(KeyMode::Normal, event(KeyCode::Char('d'), none)),
+            Some(Action::Cut)
+        );
+
+        assert!(!keymap.bind(KeyMode::Graph, Action::Paste, keys(&["p"])));
+    }
+
+    #[test]
+    fn test_names() {
+        for (action, name, _) in ACTIONS {
+            assert_eq!(Action::from_name(name), Some(action));
+        }
+        assert_eq!(KeyMode::from_name("select"), Some(KeyMode::Select));
+        assert_eq!(KeyMode::from_name("insert"), None);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::database::cell::CellData;
+use crate::database::format::NumberFormat;
+use crate::database::Database;
+use crate::display::{cell_index, column_index, generate_column_label};
+use crate::evaluator::{assign_data, assign_text, recalc};
+use crate::extensions::cond_format::CondFormat;
+use crate::utils::cell_text;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Extension of the native file format
+pub const EXTENSION: &str = ".sheet";
+
+/// Saves the sheet in the native format, a text file with one item per line:
+///   size <rows> <cols>          always first
+///   colwidth <column> <width>   for every resized column
+///   format <label> <pattern>    for every cell with a number format
+///   cf <start>:<end> <rule>     for every conditional formatting rule, in order
+///   cell <label> <text>         for every non-empty cell, text being its formula (e.g. =A1+1)
+///                               or its value, floats are written in full precision
+/// Unlike CSV, formulas and view settings survive a save and load
+pub fn save_to_sheet(
+    db: &Database,
+    cond_formats: &[CondFormat],
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "size {} {}", db.num_rows, db.num_cols)?;
+
+    for (col, width) in db.col_widths() {
+        writeln!(out, "colwidth {} {}", generate_column_label(col), width)?;
+    }
+
+    for (cell_idx, format) in db.formats() {
+        writeln!(
+            out,
+            "format {}{} {}",
+            generate_column_label(cell_idx / 1000),
+            cell_idx % 1000 + 1,
+            format
+        )?;
+    }
+
+    for cf in cond_formats {
+        writeln!(out, "cf {}:{} {}", label(cf.start), label(cf.end), cf)?;
+    }
+
+    let mut cells = db.initialized_cells();
+    cells.sort_by_key(|&cell_idx| (cell_idx % 1000, cell_idx / 1000));
+    for cell_idx in cells {
+        writeln!(
+            out,
+            "cell {}{} {}",
+            generate_column_label(cell_idx / 1000),
+            cell_idx % 1000 + 1,
+            cell_text(db, cell_idx)
+        )?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+fn label((row, col): (usize, usize)) -> String {
+    format!("{}{}", generate_column_label(col as u32), row + 1)
+}
+
+/// Loads a sheet saved by save_to_sheet, every formula is evaluated again once all cells are read
+/// Returns the sheet and its conditional formatting rules,
+/// or an error naming the line if the file is not in the native format
+pub fn load_from_sheet(path: &str) -> Result<(Database, Vec<CondFormat>), Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut db: Option<Database> = None;
+    let mut cells = Vec::new();
+    let mut cond_formats = Vec::new();
+
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        let bad_line = || format!("line {}: invalid line '{}'", line_no + 1, line);
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (keyword, rest) = line.split_once(' ').unwrap_or((&line, ""));
+        if keyword == "size" {
+            let dims: Vec<u16> = rest
+                .split_whitespace()
+                .map(|n| n.parse::<u16>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| bad_line())?;
+            match dims.as_slice() {
+                &[rows, cols] if db.is_none() && (1..=999).contains(&rows) && cols >= 1 => {
+                    db = Some(Database::new(rows, cols))
+                }
+                _ => return Err(bad_line().into()),
+            }
+            continue;
+        }
+
+        let db = db.as_mut().ok_or_else(bad_line)?;
+        let (arg, value) = rest.split_once(' ').ok_or_else(bad_line)?;
+        match keyword {
+            "colwidth" => {
+                let col = column_index(arg).ok_or_else(bad_line)?;
+                let width = value.parse::<u16>().map_err(|_| bad_line())?;
+                db.set_col_width(col, Some(width));
+            }
+            "format" => {
+                let (row, col) = cell_index(arg).ok_or_else(bad_line)?;
+                let format = NumberFormat::parse(value).ok_or_else(bad_line)?;
+                if !db.cell_in_range(1000 * col + row) {
+                    return Err(bad_line().into());
+                }
+                db.set_format(1000 * col + row, Some(format));
+            }
+            "cf" => {
+                let (start, end) = arg.split_once(':').ok_or_else(bad_line)?;
+                let (start, end) = (
+                    cell_index(start).ok_or_else(bad_line)?,
+                    cell_index(end).ok_or_else(bad_line)?,
+                );
+                if !db.cell_in_range(1000 * end.1 + end.0) {
+                    return Err(bad_line().into());
+                }
+                let cf = CondFormat::parse(
+                    value,
+                    (start.0 as usize, start.1 as usize),
+                    (end.0 as usize, end.1 as usize),
+                )
+                .map_err(|_| bad_line())?;
+                cond_formats.push(cf);
+            }
+            "cell" => {
+                let (row, col) = cell_index(arg).ok_or_else(bad_line)?;
+                let cell_idx = 1000 * col + row;
+                let ec = if value == "#ERR" {
+                    let ec = assign_data(db, cell_idx, CellData::IntData(0));
+                    let _ = db.set_error(cell_idx, true);
+                    ec
+                } else {
+                    assign_text(db, cell_idx, value)
+                };
+                if ec != 0 {
+                    return Err(bad_line().into());
+                }
+                cells.push(cell_idx);
+            }
+            _ => return Err(bad_line().into()),
+        }
+    }
+
+    let mut db = db.ok_or("missing size line")?;
+    recalc(&mut db, &cells);
+    Ok((db, cond_formats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::get_formula;
+    use crate::utils::run_command;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}{}", name, std::process::id(), EXTENSION))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut db = Database::new(20, 10);
+        run_command(&mut db, "A1=5");
+        run_command(&mut db, "A2=B1*2");
+        run_command(&mut db, "B1=A1+1");
+        run_command(&mut db, "C3=SUM(A1:B2)");
+        let _ = assign_data(&mut db, 2000, CellData::FloatData(0.125));
+        db.set_col_width(1, Some(20));
+        db.set_format(1000, NumberFormat::parse("$#,##0.00"));
+
+        let cond_formats = vec![
+            CondFormat::parse("red < 0", (0, 0), (1, 1)).unwrap(),
+            CondFormat::parse("scale red yellow green", (0, 2), (9, 2)).unwrap(),
+        ];
+
+        let path = temp_path("round_trip");
+        save_to_sheet(&db, &cond_formats, &path).unwrap();
+        let (loaded, loaded_cfs) = load_from_sheet(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((loaded.num_rows, loaded.num_cols), (20, 10));
+        assert_eq!(loaded.get_col_width(1), Some(20));
+        assert_eq!(loaded.get_col_width(0), None);
+        assert_eq!(loaded.get_format(1000), NumberFormat::parse("$#,##0.00"));
+        assert_eq!(loaded.formats().len(), 1);
+        assert_eq!(loaded_cfs, cond_formats);
+        assert!(loaded.get(1) == Ok(&CellData::IntData(12)));
+        assert!(loaded.get(2002) == Ok(&CellData::FloatData(23.0)));
+        assert!(loaded.get(2000) == Ok(&CellData::FloatData(0.125)));
+        assert_eq!(get_formula(&loaded, 1), "=B1*2");
+
+        // Formulas stay live after loading
+        let mut loaded = loaded;
+        run_command(&mut loaded, "A1=1");
+        assert!(loaded.get(1) == Ok(&CellData::IntData(4)));
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        let path = temp_path("invalid");
+        std::fs::write(&path, "cell A1 5\n").unwrap();
+        assert!(load_from_sheet(&path).is_err());
+
+        std::fs::write(&path, "size 10 10\ncell A1 =FOO(A2)\n").unwrap();
+        assert!(load_from_sheet(&path).is_err());
+
+        std::fs::write(&path, "size 10 10\nwidth A 5\n").unwrap();
+        assert!(load_from_sheet(&path).is_err());
+
+        std::fs::write(&path, "size 10 10\ncf A1:K1 red < 0\n").unwrap();
+        assert!(load_from_sheet(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
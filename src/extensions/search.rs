@@ -0,0 +1,202 @@
+use crate::database::Database;
+use crate::evaluator::{assign_text, clear, recalc};
+use crate::utils::{cell_text, get_formula};
+
+/// Checks if the displayed value or the formula of a cell contains pattern
+fn cell_matches(db: &Database, cell_idx: u32, pattern: &str) -> bool {
+    let value = match db.get(cell_idx) {
+        Ok(data) => data.to_string(),
+        Err(_) => "ERR".to_string(),
+    };
+
+    value.contains(pattern) || get_formula(db, cell_idx).contains(pattern)
+}
+
+/// Returns the (row, col) of every cell matching pattern, ordered row by row
+pub fn find_matches(db: &Database, pattern: &str) -> Vec<(usize, usize)> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+
+    let mut matches: Vec<(usize, usize)> = db
+        .initialized_cells()
+        .into_iter()
+        .filter(|&cell_idx| cell_matches(db, cell_idx, pattern))
+        .map(|cell_idx| ((cell_idx % 1000) as usize, (cell_idx / 1000) as usize))
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Returns the match after `from` (or before it if forward is false), wrapping around the sheet
+pub fn next_match(
+    matches: &[(usize, usize)],
+    from: (usize, usize),
+    forward: bool,
+) -> Option<(usize, usize)> {
+    if forward {
+        matches
+            .iter()
+            .find(|&&m| m > from)
+            .or(matches.first())
+            .copied()
+    } else {
+        matches
+            .iter()
+            .rev()
+            .find(|&&m| m < from)
+            .or(matches.last())
+            .copied()
+    }
+}
+
+/// Parses a substitution of the form "s/old/new/" (the last slash is optional)
+/// Returns (old, new), or None if it is not a valid substitution
+pub fn parse_substitution(s: &str) -> Option<(&str, &str)> {
+    let rest = s.strip_prefix("s/")?;
+    let (old, new) = rest.split_once('/')?;
+    let new = new.strip_suffix('/').unwrap_or(new);
+
+    if old.is_empty() || new.contains('/') {
+        return None;
+    }
+
+    Some((old, new))
+}
+
+/// Replaces old by new in the formula text (or value) of every cell of the block
+/// with corners start and end (inclusive), then re-parses and re-evaluates the changed cells
+/// Cells whose whole text is replaced by nothing are emptied
+/// Returns the number of changed cells and the error code of the last cell that could not be changed
+pub fn replace(
+    db: &mut Database,
+    start: (usize, usize),
+    end: (usize, usize),
+    old: &str,
+    new: &str,
+) -> (usize, i32) {
+    let (row_low, row_high) = (start.0.min(end.0), start.0.max(end.0));
+    let (col_low, col_high) = (start.1.min(end.1), start.1.max(end.1));
+
+    let mut ec = 0;
+    let mut changed = Vec::new();
+    for col in col_low..=col_high {
+        for row in row_low..=row_high {
+            let cell_idx = (1000 * col + row) as u32;
+            // Constants are rewritten from their full value, not their 2 decimal display
+            let text = cell_text(db, cell_idx);
+            if !text.contains(old) {
+                continue;
+            }
+
+            // A cell left without text is emptied, like a cut, instead of parsing an empty formula
+            let text = text.replace(old, new);
+            let res = if text.is_empty() {
+                clear(db, cell_idx)
+            } else {
+                assign_text(db, cell_idx, &text)
+            };
+            if res != 0 {
+                ec = res;
+            } else {
+                changed.push(cell_idx);
+            }
+        }
+    }
+
+    recalc(db, &changed);
+    (changed.len(), ec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::cell::CellData;
+    use crate::utils::run_command;
+
+    #[test]
+    fn test_find_matches() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=12");
+        run_command(&mut db, "C1=B2+1");
+        run_command(&mut db, "A2=5");
+        run_command(&mut db, "B3=SUM(A1:A2)");
+
+        assert_eq!(find_matches(&db, "1"), vec![(0, 0), (0, 2), (2, 1)]);
+        assert_eq!(find_matches(&db, "SUM"), vec![(2, 1)]);
+        assert_eq!(find_matches(&db, "17"), vec![(2, 1)]);
+        assert!(find_matches(&db, "").is_empty());
+    }
+
+    #[test]
+    fn test_next_match() {
+        let matches = vec![(0, 0), (0, 2), (2, 1)];
+        assert_eq!(next_match(&matches, (0, 0), true), Some((0, 2)));
+        assert_eq!(next_match(&matches, (1, 5), true), Some((2, 1)));
+        assert_eq!(next_match(&matches, (2, 1), true), Some((0, 0)));
+        assert_eq!(next_match(&matches, (0, 2), false), Some((0, 0)));
+        assert_eq!(next_match(&matches, (0, 0), false), Some((2, 1)));
+        assert_eq!(next_match(&[], (0, 0), false), None);
+    }
+
+    #[test]
+    fn test_parse_substitution() {
+        assert_eq!(parse_substitution("s/A1/B1/"), Some(("A1", "B1")));
+        assert_eq!(parse_substitution("s/SUM/MAX"), Some(("SUM", "MAX")));
+        assert_eq!(parse_substitution("s/5//"), Some(("5", "")));
+        assert_eq!(parse_substitution("s//x/"), None);
+        assert_eq!(parse_substitution("s/a/b/c"), None);
+        assert_eq!(parse_substitution("sort"), None);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=1");
+        run_command(&mut db, "A2=2");
+        run_command(&mut db, "B1=SUM(A1:A2)");
+        run_command(&mut db, "B2=A1*3");
+        run_command(&mut db, "B3=13");
+        run_command(&mut db, "C1=B2+1");
+
+        assert_eq!(replace(&mut db, (0, 1), (2, 1), "A1", "A2"), (2, 0));
+        assert!(db.get(1000) == Ok(&CellData::FloatData(2.0)));
+        assert!(db.get(1001) == Ok(&CellData::IntData(6)));
+        assert!(db.get(2000) == Ok(&CellData::IntData(7)));
+
+        assert_eq!(replace(&mut db, (0, 1), (2, 1), "3", "4"), (2, 0));
+        assert!(db.get(1001) == Ok(&CellData::IntData(8)));
+        assert!(db.get(1002) == Ok(&CellData::IntData(14)));
+
+        // Invalid formulas are left untouched
+        assert_eq!(replace(&mut db, (0, 1), (0, 1), "SUM", "FOO"), (0, 1));
+        assert!(db.get(1000) == Ok(&CellData::FloatData(2.0)));
+    }
+
+    #[test]
+    fn test_replace_to_empty() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=5");
+        run_command(&mut db, "A2=15");
+        run_command(&mut db, "B1=A1+A2");
+
+        // A1 is emptied, A2 keeps what is left of its text
+        assert_eq!(replace(&mut db, (0, 0), (1, 0), "5", ""), (2, 0));
+        assert!(db.is_cell_initialized(0) == Ok(false));
+        assert_eq!(get_formula(&db, 0), "");
+        assert!(db.get(1) == Ok(&CellData::IntData(1)));
+        assert!(db.get(1000) == Ok(&CellData::IntData(1)));
+    }
+
+    #[test]
+    fn test_replace_keeps_float_precision() {
+        let mut db = Database::new(10, 10);
+        let _ = db.set_float(0, 1.23456);
+        let _ = db.set_float(1, 41.5);
+
+        // Only the replaced digits change, floats are not rounded to their display
+        assert_eq!(replace(&mut db, (0, 0), (1, 0), "4", "9"), (2, 0));
+        assert!(db.get(0) == Ok(&CellData::FloatData(1.23956)));
+        assert!(db.get(1) == Ok(&CellData::FloatData(91.5)));
+    }
+}
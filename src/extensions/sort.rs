@@ -0,0 +1,275 @@
+use crate::database::cell::{Cell, CellData};
+use crate::database::Database;
+use crate::display::column_index;
+use crate::evaluator::{clear, recalc};
+use crate::extensions::clipboard::paste_cell;
+use std::cmp::Ordering;
+
+/// Parses sort keys of the form "B desc, A asc, C"
+/// Each key is a column label inside col_low..=col_high with an optional order, ascending by default
+/// An empty string sorts by the first column
+/// Returns the (column, descending) pairs, or an error message
+pub fn parse_keys(
+    s: &str,
+    col_low: usize,
+    col_high: usize,
+) -> Result<Vec<(usize, bool)>, &'static str> {
+    if s.trim().is_empty() {
+        return Ok(vec![(col_low, false)]);
+    }
+
+    let mut keys = Vec::new();
+    for key in s.split(',') {
+        let parts: Vec<&str> = key.split_ascii_whitespace().collect();
+        let desc = match parts.as_slice() {
+            [_] => false,
+            [_, "asc"] => false,
+            [_, "desc"] => true,
+            _ => return Err("Sort keys must look like :sort B desc, A asc"),
+        };
+
+        let col = match column_index(parts[0]) {
+            Some(col) => col as usize,
+            None => return Err("Invalid column in sort key"),
+        };
+        if col < col_low || col > col_high {
+            return Err("Sort column is outside the selection");
+        }
+
+        keys.push((col, desc));
+    }
+
+    Ok(keys)
+}
+
+/// Sorts the rows of the block with corners start and end (inclusive) by keys
+/// Numbers are ordered by value, errors come after numbers and empty cells always come last
+/// Formulas keep their relative references, i.e. they are shifted by the distance their row moved
+/// If a moved formula cannot be written, e.g. its references leave the sheet or form a cycle,
+/// the block is put back as it was
+/// Returns 0, or the error code of the cell that could not be moved
+pub fn sort_rows(
+    db: &mut Database,
+    start: (usize, usize),
+    end: (usize, usize),
+    keys: &[(usize, bool)],
+) -> i32 {
+    let (row_low, row_high) = (start.0.min(end.0), start.0.max(end.0));
+    let (col_low, col_high) = (start.1.min(end.1), start.1.max(end.1));
+
+    let mut order: Vec<usize> = (row_low..=row_high).collect();
+    order.sort_by(|&a, &b| {
+        for &(col, desc) in keys {
+            let ord = compare(
+                db.get((1000 * col + a) as u32),
+                db.get((1000 * col + b) as u32),
+                desc,
+            );
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    });
+
+    if order.iter().copied().eq(row_low..=row_high) {
+        return 0;
+    }
+
+    // Snapshot the block, write_rows empties it so that stale formulas cannot form cycles while rows are written
+    let snapshot: Vec<Vec<_>> = order
+        .iter()
+        .map(|&row| {
+            (col_low..=col_high)
+                .map(|col| db.get_cell_clone((1000 * col + row) as u32).ok())
+                .collect()
+        })
+        .collect();
+
+    let sorted_rows: Vec<usize> = (row_low..=row_high).collect();
+    let mut ec = write_rows(db, &snapshot, &order, &sorted_rows, col_low);
+    if ec != 0 {
+        // Each row goes back where it was, so no reference is shifted
+        let res = write_rows(db, &snapshot, &order, &order, col_low);
+        if res != 0 {
+            ec = res;
+        }
+    }
+
+    let moved: Vec<u32> = (col_low..=col_high)
+        .flat_map(|col| (row_low..=row_high).map(move |row| (1000 * col + row) as u32))
+        .collect();
+    recalc(db, &moved);
+    ec
+}
+
+/// Empties the rows of the block, then writes rows[i] of the snapshot, taken from row old_rows[i],
+/// into row new_rows[i], starting at column col_low
+/// Stops at the first cell that cannot be written and returns its error code, 0 if every cell was written
+fn write_rows(
+    db: &mut Database,
+    rows: &[Vec<Option<Cell>>],
+    old_rows: &[usize],
+    new_rows: &[usize],
+    col_low: usize,
+) -> i32 {
+    for &row in new_rows {
+        for col in col_low..col_low + rows[0].len() {
+            clear(db, (1000 * col + row) as u32);
+        }
+    }
+
+    for ((cells, &old_row), &new_row) in rows.iter().zip(old_rows).zip(new_rows) {
+        for (j, cell) in cells.iter().enumerate() {
+            let cell_idx = (1000 * (col_low + j) + new_row) as u32;
+            let res = paste_cell(
+                db,
+                cell_idx,
+                cell.as_ref(),
+                new_row as i64 - old_row as i64,
+                0,
+            );
+            if res != 0 {
+                return res;
+            }
+        }
+    }
+    0
+}
+
+/// Orders two cells for sorting, empty cells last and errors just before them regardless of desc
+fn compare(a: Result<&CellData, bool>, b: Result<&CellData, bool>, desc: bool) -> Ordering {
+    fn rank(data: &Result<&CellData, bool>) -> u8 {
+        match data {
+            Ok(CellData::Empty) => 2,
+            Ok(_) => 0,
+            Err(_) => 1,
+        }
+    }
+
+    fn value(data: &Result<&CellData, bool>) -> f32 {
+        match data {
+            Ok(CellData::IntData(i)) => *i as f32,
+            Ok(CellData::FloatData(f)) => *f,
+            _ => 0.0,
+        }
+    }
+
+    match rank(&a).cmp(&rank(&b)) {
+        Ordering::Equal if rank(&a) == 0 => {
+            let ord = value(&a).total_cmp(&value(&b));
+            if desc {
+                ord.reverse()
+            } else {
+                ord
+            }
+        }
+        ord => ord,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{get_formula, run_command};
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys("", 1, 3), Ok(vec![(1, false)]));
+        assert_eq!(
+            parse_keys("C desc, B asc,D", 1, 3),
+            Ok(vec![(2, true), (1, false), (3, false)])
+        );
+        assert!(parse_keys("A", 1, 3).is_err());
+        assert!(parse_keys("B down", 1, 3).is_err());
+        assert!(parse_keys("B,", 1, 3).is_err());
+    }
+
+    #[test]
+    fn test_sort_single_key() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=3");
+        run_command(&mut db, "A2=1");
+        run_command(&mut db, "A4=2");
+        run_command(&mut db, "B1=30");
+        run_command(&mut db, "B2=10");
+        run_command(&mut db, "B3=99");
+        run_command(&mut db, "B4=20");
+
+        assert_eq!(sort_rows(&mut db, (0, 0), (3, 1), &[(0, false)]), 0);
+        let col_a: Vec<_> = (0..4).map(|r| *db.get(r).unwrap()).collect();
+        let col_b: Vec<_> = (1000..1004).map(|r| *db.get(r).unwrap()).collect();
+        assert_eq!(
+            col_a,
+            vec![
+                CellData::IntData(1),
+                CellData::IntData(2),
+                CellData::IntData(3),
+                CellData::Empty
+            ]
+        );
+        assert_eq!(
+            col_b,
+            vec![
+                CellData::IntData(10),
+                CellData::IntData(20),
+                CellData::IntData(30),
+                CellData::IntData(99)
+            ]
+        );
+
+        assert_eq!(sort_rows(&mut db, (0, 0), (3, 1), &[(0, true)]), 0);
+        assert!(db.get(0) == Ok(&CellData::IntData(3)));
+        assert!(db.get(3) == Ok(&CellData::Empty));
+    }
+
+    #[test]
+    fn test_sort_multiple_keys_and_formulas() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=2");
+        run_command(&mut db, "A2=1");
+        run_command(&mut db, "A3=2");
+        run_command(&mut db, "B1=5");
+        run_command(&mut db, "B2=7");
+        run_command(&mut db, "B3=9");
+        run_command(&mut db, "C1=B1*2");
+        run_command(&mut db, "C2=B2*2");
+        run_command(&mut db, "C3=B3*2");
+        run_command(&mut db, "D1=SUM(C1:C3)");
+
+        assert_eq!(
+            sort_rows(&mut db, (0, 0), (2, 2), &[(0, false), (1, true)]),
+            0
+        );
+        assert!(db.get(1000) == Ok(&CellData::IntData(7)));
+        assert!(db.get(1001) == Ok(&CellData::IntData(9)));
+        assert!(db.get(1002) == Ok(&CellData::IntData(5)));
+        assert!(db.get(2001) == Ok(&CellData::IntData(18)));
+
+        // Formulas follow their rows and stay live
+        run_command(&mut db, "B2=1");
+        assert!(db.get(2001) == Ok(&CellData::IntData(2)));
+        assert!(db.get(3000) == Ok(&CellData::FloatData(26.0)));
+    }
+
+    #[test]
+    fn test_sort_failure_keeps_rows() {
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=5");
+        run_command(&mut db, "A2=1");
+        run_command(&mut db, "B1=7");
+        run_command(&mut db, "B2=B1+1");
+
+        // B2 refers to the row above, so it cannot be moved to the first row
+        assert_eq!(sort_rows(&mut db, (0, 0), (1, 1), &[(0, false)]), 4);
+        assert!(db.get(0) == Ok(&CellData::IntData(5)));
+        assert!(db.get(1) == Ok(&CellData::IntData(1)));
+        assert!(db.get(1000) == Ok(&CellData::IntData(7)));
+        assert!(db.get(1001) == Ok(&CellData::IntData(8)));
+        assert_eq!(get_formula(&db, 1001), "=B1+1");
+
+        // The restored formula is still live
+        run_command(&mut db, "B1=10");
+        assert!(db.get(1001) == Ok(&CellData::IntData(11)));
+    }
+}
@@ -0,0 +1,103 @@
+/// Summary statistics of the numbers of a selection
+/// stdev is the population standard deviation, like the STDEV function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub sum: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub stdev: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Summary {
+    /// Computes the statistics of values, None if there are no values
+    pub fn from_values(values: &[f32]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let count = sorted.len();
+        let sum: f32 = sorted.iter().sum();
+        let mean = sum / count as f32;
+        let median = if count % 2 == 1 {
+            sorted[count / 2]
+        } else {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        };
+        let var = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / count as f32;
+
+        Some(Summary {
+            count,
+            sum,
+            mean,
+            median,
+            stdev: var.sqrt(),
+            min: sorted[0],
+            max: sorted[count - 1],
+        })
+    }
+}
+
+/// Splits min..=max of values into the given number of buckets of equal width
+/// Returns the lower bound of every bucket and the number of values in it,
+/// the largest value falls in the last bucket
+/// All values go in a single bucket if they are equal
+pub fn histogram(values: &[f32], buckets: usize) -> Vec<(f32, u64)> {
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if values.is_empty() || buckets == 0 {
+        return vec![];
+    }
+    if max <= min {
+        return vec![(min, values.len() as u64)];
+    }
+
+    let width = (max - min) / buckets as f32;
+    let mut counts = vec![0u64; buckets];
+    for v in values {
+        let idx = (((v - min) / width) as usize).min(buckets - 1);
+        counts[idx] += 1;
+    }
+
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(idx, count)| (min + width * idx as f32, count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(Summary::from_values(&[]), None);
+
+        let s = Summary::from_values(&[4.0, -2.0, 8.0, 2.0]).unwrap();
+        assert_eq!(s.count, 4);
+        assert_eq!(s.sum, 12.0);
+        assert_eq!(s.mean, 3.0);
+        assert_eq!(s.median, 3.0);
+        assert_eq!(s.stdev, 13.0f32.sqrt());
+        assert_eq!((s.min, s.max), (-2.0, 8.0));
+
+        let s = Summary::from_values(&[5.0, 1.0, 3.0]).unwrap();
+        assert_eq!(s.median, 3.0);
+    }
+
+    #[test]
+    fn test_histogram() {
+        assert!(histogram(&[], 4).is_empty());
+        assert_eq!(histogram(&[2.0, 2.0], 4), vec![(2.0, 2)]);
+        assert_eq!(
+            histogram(&[0.0, 1.0, 2.5, 3.9, 4.0, 4.0], 4),
+            vec![(0.0, 1), (1.0, 1), (2.0, 1), (3.0, 3)]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use crate::extensions::app::App;
+use crate::extensions::chart::{ChartData, ChartKind};
+use crate::extensions::ui::short_number;
+use crate::utils::extract_range_data;
+use std::error::Error;
+use std::fs;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 400.0;
+/// Room around the plot for the title, tick labels and legend
+const MARGIN: f64 = 60.0;
+
+/// Colors of the series, in the order of the TUI chart colors
+const SERIES_COLORS: [&str; 6] = [
+    "#2ca02c", "#17becf", "#bcbd22", "#e377c2", "#d62728", "#1f77b4",
+];
+
+/// Writes the chart of the selection shown in Graph mode (app.chart) to path as a standalone SVG file
+/// Bar charts and sparklines use the values of extract_range_data, line and scatter charts use ChartData
+pub fn export_chart(app: &App, path: &str) -> Result<(), Box<dyn Error>> {
+    let (start, end) = app.selection().ok_or("no selection to export")?;
+    let title = app.chart.title();
+
+    let svg = match app.chart {
+        ChartKind::Bar => bar_svg(title, &extract_range_data(app)),
+        ChartKind::Line | ChartKind::Scatter => xy_svg(
+            title,
+            app.chart,
+            &ChartData::from_range(&app.db, start, end),
+        ),
+        ChartKind::Sparkline => {
+            let values: Vec<f32> = extract_range_data(app).iter().map(|(_, v)| *v).collect();
+            sparkline_svg(title, &values)
+        }
+    };
+
+    fs::write(path, svg)?;
+    Ok(())
+}
+
+/// Bar chart with one bar per cell, labelled with the cell, negative bars go below the zero line
+pub fn bar_svg(title: &str, data: &[(String, f32)]) -> String {
+    let mut elems = vec![title_elem(title)];
+    if data.is_empty() {
+        elems.push(text(
+            WIDTH / 2.0,
+            HEIGHT / 2.0,
+            "middle",
+            "No numbers to plot",
+        ));
+        return document(elems);
+    }
+
+    let low = data.iter().map(|(_, v)| *v as f64).fold(0.0, f64::min);
+    let high = data.iter().map(|(_, v)| *v as f64).fold(0.0, f64::max);
+    let (low, high) = if low == high {
+        (low, high + 1.0)
+    } else {
+        (low, high)
+    };
+    let y = |v: f64| scale(v, low, high, HEIGHT - MARGIN, MARGIN);
+
+    elems.extend(y_axis(low, high));
+    elems.push(line(MARGIN, y(0.0), WIDTH - MARGIN, y(0.0)));
+
+    let slot = (WIDTH - 2.0 * MARGIN) / data.len() as f64;
+    for (idx, (label, value)) in data.iter().enumerate() {
+        // Labelled like the bars of the TUI chart
+        let value_text = short_number(*value);
+        let value = *value as f64;
+        let x = MARGIN + slot * idx as f64;
+        let (top, bottom) = (y(value.max(0.0)), y(value.min(0.0)));
+        elems.push(format!(
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+            x + slot * 0.1,
+            top,
+            slot * 0.8,
+            bottom - top,
+            SERIES_COLORS[0]
+        ));
+        elems.push(text(x + slot / 2.0, top - 4.0, "middle", &value_text));
+        elems.push(text(
+            x + slot / 2.0,
+            HEIGHT - MARGIN + 16.0,
+            "middle",
+            label,
+        ));
+    }
+
+    document(elems)
+}
+
+/// Line or scatter chart of the series of data, with a legend naming the series
+pub fn xy_svg(title: &str, kind: ChartKind, data: &ChartData) -> String {
+    let mut elems = vec![title_elem(title)];
+    let ((x_min, x_max), (y_min, y_max)) = match data.bounds() {
+        Some(bounds) => bounds,
+        None => {
+            elems.push(text(
+                WIDTH / 2.0,
+                HEIGHT / 2.0,
+                "middle",
+                "No numbers to plot",
+            ));
+            return document(elems);
+        }
+    };
+    let x = |v: f64| scale(v, x_min, x_max, MARGIN, WIDTH - MARGIN);
+    let y = |v: f64| scale(v, y_min, y_max, HEIGHT - MARGIN, MARGIN);
+
+    elems.extend(y_axis(y_min, y_max));
+    elems.push(line(
+        MARGIN,
+        HEIGHT - MARGIN,
+        WIDTH - MARGIN,
+        HEIGHT - MARGIN,
+    ));
+    for v in ticks(x_min, x_max) {
+        elems.push(text(
+            x(v),
+            HEIGHT - MARGIN + 16.0,
+            "middle",
+            &format!("{:.2}", v),
+        ));
+    }
+    elems.push(text(WIDTH / 2.0, HEIGHT - 12.0, "middle", &data.x_title));
+
+    for (idx, ((name, points), color)) in data
+        .series
+        .iter()
+        .zip(SERIES_COLORS.iter().cycle())
+        .enumerate()
+    {
+        if kind == ChartKind::Line {
+            elems.push(polyline(
+                points.iter().map(|&(px, py)| (x(px), y(py))),
+                color,
+            ));
+        } else {
+            for &(px, py) in points {
+                elems.push(format!(
+                    r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#,
+                    x(px),
+                    y(py),
+                    color
+                ));
+            }
+        }
+
+        let legend_y = MARGIN + 16.0 * idx as f64;
+        elems.push(format!(
+            r#"<rect x="{:.1}" y="{:.1}" width="10" height="10" fill="{}"/>"#,
+            WIDTH - MARGIN + 8.0,
+            legend_y - 9.0,
+            color
+        ));
+        elems.push(text(WIDTH - MARGIN + 22.0, legend_y, "start", name));
+    }
+
+    document(elems)
+}
+
+/// Values drawn as a line scaled between their smallest and largest value, without axes
+pub fn sparkline_svg(title: &str, values: &[f32]) -> String {
+    let mut elems = vec![title_elem(title)];
+    if values.is_empty() {
+        elems.push(text(
+            WIDTH / 2.0,
+            HEIGHT / 2.0,
+            "middle",
+            "No numbers to plot",
+        ));
+        return document(elems);
+    }
+
+    let min = values
+        .iter()
+        .map(|v| *v as f64)
+        .fold(f64::INFINITY, f64::min);
+    let max = values
+        .iter()
+        .map(|v| *v as f64)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let last = (values.len() - 1).max(1) as f64;
+    elems.push(polyline(
+        values.iter().enumerate().map(|(idx, v)| {
+            let y = if max > min {
+                scale(*v as f64, min, max, HEIGHT - MARGIN, MARGIN)
+            } else {
+                HEIGHT / 2.0
+            };
+            (scale(idx as f64, 0.0, last, MARGIN, WIDTH - MARGIN), y)
+        }),
+        SERIES_COLORS[0],
+    ));
+    elems.push(text(
+        WIDTH / 2.0,
+        HEIGHT - 12.0,
+        "middle",
+        &format!("min {:.2}, max {:.2}", min, max),
+    ));
+
+    document(elems)
+}
+
+fn document(elems: Vec<String>) -> String {
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="11">"#,
+            "\n",
+            r#"<rect width="100%" height="100%" fill="white"/>"#,
+            "\n{}\n</svg>\n"
+        ),
+        elems.join("\n"),
+        w = WIDTH,
+        h = HEIGHT
+    )
+}
+
+/// Maps v from low..high to the pixel range from..to
+fn scale(v: f64, low: f64, high: f64, from: f64, to: f64) -> f64 {
+    from + (v - low) / (high - low) * (to - from)
+}
+
+/// Values labelled on an axis, the same three as the TUI charts
+fn ticks(low: f64, high: f64) -> [f64; 3] {
+    [low, (low + high) / 2.0, high]
+}
+
+fn y_axis(low: f64, high: f64) -> Vec<String> {
+    let mut elems = vec![line(MARGIN, MARGIN, MARGIN, HEIGHT - MARGIN)];
+    for v in ticks(low, high) {
+        let y = scale(v, low, high, HEIGHT - MARGIN, MARGIN);
+        elems.push(text(MARGIN - 6.0, y + 4.0, "end", &format!("{:.2}", v)));
+    }
+    elems
+}
+
+fn title_elem(title: &str) -> String {
+    format!(
+        r#"<text x="{:.1}" y="24" text-anchor="middle" font-size="16">{}</text>"#,
+        WIDTH / 2.0,
+        escape(title)
+    )
+}
+
+fn text(x: f64, y: f64, anchor: &str, s: &str) -> String {
+    format!(
+        r#"<text x="{:.1}" y="{:.1}" text-anchor="{}">{}</text>"#,
+        x,
+        y,
+        anchor,
+        escape(s)
+    )
+}
+
+fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> String {
+    format!(
+        r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="black"/>"#,
+        x1, y1, x2, y2
+    )
+}
+
+fn polyline(points: impl Iterator<Item = (f64, f64)>, color: &str) -> String {
+    let points: Vec<String> = points.map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+    format!(
+        r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="2"/>"#,
+        points.join(" "),
+        color
+    )
+}
+
+/// Escapes the characters with a meaning in XML
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::extensions::app::Mode;
+
+    #[test]
+    fn test_bar_svg() {
+        let svg = bar_svg(
+            "Bar Chart",
+            &[("A1".to_string(), 4.0), ("A2".to_string(), -2.5)],
+        );
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect ").count(), 3);
+        assert!(svg.contains(">A2</text>"));
+        assert!(svg.contains(">-2.5</text>"));
+
+        assert!(bar_svg("Bar Chart", &[]).contains("No numbers to plot"));
+    }
+
+    #[test]
+    fn test_export_chart() {
+        let path = std::env::temp_dir()
+            .join(format!("export_test_{}.svg", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut app = App::new(Database::new(5, 5));
+        let _ = app.db.set_float(0, 0.1);
+        let _ = app.db.set_int(1, 3);
+        app.mode = Mode::Graph((0, 0), (1, 0));
+
+        export_chart(&app, &path).unwrap();
+        let svg = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(svg.contains(">0.1</text>"));
+        assert!(svg.contains(">3</text>"));
+        assert!(!svg.contains("0.1000"));
+    }
+
+    #[test]
+    fn test_xy_svg() {
+        let data = ChartData {
+            x_title: "A & B".to_string(),
+            series: vec![
+                ("B".to_string(), vec![(1.0, 2.0), (2.0, 3.0)]),
+                ("C".to_string(), vec![(1.0, 5.0)]),
+            ],
+        };
+
+        let svg = xy_svg("Line Chart", ChartKind::Line, &data);
+        assert_eq!(svg.matches("<polyline ").count(), 2);
+        assert!(svg.contains(">A &amp; B</text>"));
+        assert!(svg.contains(">C</text>"));
+
+        let svg = xy_svg("Scatter Chart", ChartKind::Scatter, &data);
+        assert_eq!(svg.matches("<circle ").count(), 3);
+    }
+
+    #[test]
+    fn test_sparkline_svg() {
+        let svg = sparkline_svg("Sparkline", &[1.0, 3.0, 2.0]);
+        assert!(svg.contains(r#"points="60.0,340.0 320.0,60.0 580.0,200.0""#));
+        assert!(svg.contains("min 1.00, max 3.00"));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::database::Database;
+use crate::extensions::cond_format::CondFormat;
+use crate::extensions::native::{load_from_sheet, save_to_sheet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Extension added to the name of a swap file
+const SWAP_EXTENSION: &str = ".swp";
+
+/// Path of the swap file of file, hidden next to it: dir/data.csv gives dir/.data.csv.swp
+pub fn swap_path(file: &str) -> String {
+    let path = Path::new(file);
+    let name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+    path.with_file_name(format!(".{}{}", name, SWAP_EXTENSION))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Writes the sheet to the swap file of file
+/// The native format is used whatever the file is, so formulas survive a crash
+pub fn write_swap(
+    db: &Database,
+    cond_formats: &[CondFormat],
+    file: &str,
+) -> Result<(), Box<dyn Error>> {
+    save_to_sheet(db, cond_formats, &swap_path(file))
+}
+
+/// Checks if file has a swap file, which is left behind by a session that did not exit cleanly
+pub fn has_swap(file: &str) -> bool {
+    Path::new(&swap_path(file)).exists()
+}
+
+/// Loads the sheet and conditional formatting rules saved in the swap file of file
+pub fn load_swap(file: &str) -> Result<(Database, Vec<CondFormat>), Box<dyn Error>> {
+    load_from_sheet(&swap_path(file))
+}
+
+/// Deletes the swap file of file, if there is one
+pub fn remove_swap(file: &str) {
+    let _ = fs::remove_file(swap_path(file));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::cell::CellData;
+    use crate::utils::{get_formula, run_command};
+
+    #[test]
+    fn test_swap_path() {
+        assert_eq!(swap_path("data.csv"), ".data.csv.swp");
+        assert_eq!(
+            swap_path("dir/work.sheet"),
+            format!("dir{}.work.sheet.swp", std::path::MAIN_SEPARATOR)
+        );
+    }
+
+    #[test]
+    fn test_write_and_load() {
+        let file = std::env::temp_dir()
+            .join(format!("swap_test_{}.csv", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        assert!(!has_swap(&file));
+
+        let mut db = Database::new(10, 10);
+        run_command(&mut db, "A1=5");
+        run_command(&mut db, "A2=A1*2");
+        write_swap(&db, &[], &file).unwrap();
+        assert!(has_swap(&file));
+        // The swap file is next to the file, which is never written
+        assert!(!Path::new(&file).exists());
+
+        let (loaded, cond_formats) = load_swap(&file).unwrap();
+        assert!(loaded.get(1) == Ok(&CellData::IntData(10)));
+        assert_eq!(get_formula(&loaded, 1), "=A1*2");
+        assert!(cond_formats.is_empty());
+
+        remove_swap(&file);
+        assert!(!has_swap(&file));
+    }
+}
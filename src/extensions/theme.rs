@@ -0,0 +1,134 @@
+use ratatui::style::{Color, Modifier, Style};
+
+/// Colors of the TUI
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Cells and the border of the sheet
+    pub grid: Style,
+    /// Column labels
+    pub header: Style,
+    /// Row numbers
+    pub row_label: Style,
+    /// Current cell and selected range
+    pub selection: Style,
+    /// Error messages
+    pub error: Style,
+    pub status_bar: Style,
+    /// Chart and statistics popups
+    pub popup: Style,
+    /// Colors of the chart series, reused in order, bars use the first one
+    pub chart: [Color; 6],
+}
+
+pub const DARK: Theme = Theme {
+    name: "dark",
+    grid: Style::new(),
+    header: Style::new().add_modifier(Modifier::BOLD),
+    row_label: Style::new()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::BOLD),
+    selection: Style::new()
+        .fg(Color::Black)
+        .bg(Color::Yellow)
+        .add_modifier(Modifier::BOLD),
+    error: Style::new().fg(Color::Red),
+    status_bar: Style::new(),
+    popup: Style::new().bg(Color::Black),
+    chart: [
+        Color::Green,
+        Color::Cyan,
+        Color::Yellow,
+        Color::Magenta,
+        Color::LightRed,
+        Color::LightBlue,
+    ],
+};
+
+pub const LIGHT: Theme = Theme {
+    name: "light",
+    grid: Style::new().fg(Color::Black).bg(Color::White),
+    header: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+    row_label: Style::new()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::BOLD),
+    selection: Style::new()
+        .fg(Color::White)
+        .bg(Color::Blue)
+        .add_modifier(Modifier::BOLD),
+    error: Style::new().fg(Color::Red).add_modifier(Modifier::BOLD),
+    status_bar: Style::new().fg(Color::Black).bg(Color::Gray),
+    popup: Style::new().fg(Color::Black).bg(Color::White),
+    chart: [
+        Color::Blue,
+        Color::Red,
+        Color::Green,
+        Color::Magenta,
+        Color::Cyan,
+        Color::DarkGray,
+    ],
+};
+
+pub const HIGH_CONTRAST: Theme = Theme {
+    name: "high-contrast",
+    grid: Style::new().fg(Color::White).bg(Color::Black),
+    header: Style::new()
+        .fg(Color::LightYellow)
+        .add_modifier(Modifier::BOLD)
+        .add_modifier(Modifier::UNDERLINED),
+    row_label: Style::new().fg(Color::White).add_modifier(Modifier::BOLD),
+    selection: Style::new()
+        .fg(Color::Black)
+        .bg(Color::White)
+        .add_modifier(Modifier::BOLD),
+    error: Style::new()
+        .fg(Color::Black)
+        .bg(Color::LightRed)
+        .add_modifier(Modifier::BOLD),
+    status_bar: Style::new().fg(Color::Black).bg(Color::White),
+    popup: Style::new().fg(Color::White).bg(Color::Black),
+    chart: [
+        Color::LightYellow,
+        Color::LightCyan,
+        Color::LightGreen,
+        Color::LightMagenta,
+        Color::White,
+        Color::LightRed,
+    ],
+};
+
+/// Every theme, selected by name with :theme or in the config file
+pub const THEMES: [Theme; 3] = [DARK, LIGHT, HIGH_CONTRAST];
+
+impl Theme {
+    pub fn by_name(name: &str) -> Option<Theme> {
+        THEMES.into_iter().find(|theme| theme.name == name)
+    }
+
+    /// Style of the text written on a bar of a bar chart
+    pub fn bar_value(&self) -> Style {
+        Style::new()
+            .fg(self.popup.bg.unwrap_or(Color::Black))
+            .bg(self.chart[0])
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        DARK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_by_name() {
+        for theme in THEMES {
+            assert_eq!(Theme::by_name(theme.name), Some(theme));
+        }
+        assert_eq!(Theme::by_name("neon"), None);
+        assert_eq!(Theme::default().name, "dark");
+    }
+}
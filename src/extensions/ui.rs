@@ -1,19 +1,26 @@
 use crate::display::generate_column_label;
 use crate::extensions::app::{App, Mode};
+use crate::extensions::chart::{sparkline_data, ChartData, ChartKind};
+use crate::extensions::help::help_lines;
+use crate::extensions::stats::{histogram, Summary};
+use crate::extensions::swap::swap_path;
 use crate::utils::{centered_rect, extract_range_data, get_formula, transform_data_for_barchart};
 use ratatui::layout::Alignment;
 use ratatui::text::Text;
 use ratatui::{
     backend::Backend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
-    text::Line,
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, Paragraph, Row, Table},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    symbols,
+    text::{Line, Span},
+    widgets::{
+        Axis, Bar, BarChart, BarGroup, Block, Borders, Cell, Chart, Clear, Dataset, GraphType,
+        Paragraph, Row, Sparkline, Table,
+    },
     Frame,
 };
 
 const ROWLABELW: u16 = 5;
-const COLWIDTH: u16 = 14;
 
 pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
@@ -24,31 +31,75 @@ pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
 
     let sheet_height = chunks[0].height.saturating_sub(3);
 
-    let num_cols_max = (chunks[0].width.saturating_sub(2 + ROWLABELW)) / COLWIDTH;
+    // Frozen columns come first, then the columns from topleft
+    app.sheet_width = chunks[0].width.saturating_sub(2 + ROWLABELW);
+    let shown_cols = app.shown_cols();
 
-    let max_row = (app.topleft.0 + sheet_height as usize).min(app.db.num_rows as usize);
-    let max_col = (app.topleft.1 + num_cols_max as usize).min(app.db.num_cols as usize);
+    // Frozen rows come first, then the rows from topleft, rows hidden by the filter are skipped
+    let shown_rows: Vec<usize> = (0..app.frozen.0)
+        .chain(app.topleft.0..app.db.num_rows as usize)
+        .filter(|&r| app.row_visible(r))
+        .take(sheet_height as usize)
+        .collect();
 
-    let sz = (max_row - app.topleft.0 - 1, max_col - app.topleft.1 - 1);
+    // The display size only counts the scrolling rows and columns
+    let sz = (
+        shown_rows
+            .iter()
+            .filter(|&&r| r >= app.frozen.0)
+            .count()
+            .saturating_sub(1),
+        shown_cols
+            .iter()
+            .filter(|&&c| c >= app.frozen.1)
+            .count()
+            .saturating_sub(1),
+    );
     app.set_dissz(sz);
 
+    // Screen positions of the shown cells, for mapping mouse clicks back to cells
+    // Inside the border, the header takes the first line and the row labels the first columns
+    app.screen_rows = shown_rows
+        .iter()
+        .enumerate()
+        .map(|(i, &r)| (chunks[0].y + 2 + i as u16, r))
+        .collect();
+    let mut x = chunks[0].x + 1 + ROWLABELW + 1;
+    let mut screen_cols = Vec::with_capacity(shown_cols.len());
+    for &c in &shown_cols {
+        let width = app.col_width(c);
+        screen_cols.push((x, width, c));
+        x = x.saturating_add(width + 1);
+    }
+    app.screen_cols = screen_cols;
+
     // Add one extra column for row numbers
     let header = Row::new(
         std::iter::once("".to_string()) // Row label for header column
-            .chain((app.topleft.1..max_col).map(|c| generate_column_label(c as u32))),
+            .chain(shown_cols.iter().map(|&c| generate_column_label(c as u32))),
     )
-    .style(Style::default().add_modifier(Modifier::BOLD));
+    .style(app.theme.header);
+
+    // Range statistics of the conditional formatting rules, computed once per render
+    let cond_formats: Vec<_> = app
+        .cond_formats
+        .iter()
+        .filter_map(|cf| cf.range_stats(&app.db).map(|stats| (cf, stats)))
+        .collect();
 
-    let rows = (app.topleft.0..max_row).map(|r: usize| {
+    let rows = shown_rows.into_iter().map(|r: usize| {
         let row_number_cell = Cell::from(format!("{}", r + 1)) // row number as first cell
-            .style(
-                Style::default()
-                    .fg(Color::DarkGray)
-                    .add_modifier(Modifier::BOLD),
-            );
+            .style(app.theme.row_label);
 
-        let data_cells = (app.topleft.1..max_col).map(|c| {
+        let data_cells = shown_cols.iter().map(|&c| {
             let mut cell = Cell::from(app.get_value(r, c));
+            // Later rules are drawn over earlier ones
+            let cf_style = cond_formats
+                .iter()
+                .filter(|(cf, _)| cf.contains(r, c))
+                .filter_map(|(cf, stats)| cf.style(app.db.get((1000 * c + r) as u32), *stats))
+                .fold(Style::default(), |style, cf_style| style.patch(cf_style));
+            cell = cell.style(cf_style);
             match app.mode {
                 Mode::Select(rs, cs) => {
                     let (row_start, row_end) = if rs <= app.selected.0 {
@@ -63,27 +114,16 @@ pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
                     };
 
                     if (row_start..=row_end).contains(&r) && (col_start..=col_end).contains(&c) {
-                        cell = cell.style(
-                            Style::default()
-                                .fg(Color::Black)
-                                .bg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD),
-                        );
+                        cell = cell.style(app.theme.selection);
                     }
                 }
                 _ => {}
             }
             if app.selected == (r, c) {
                 if app.mode == Mode::Insert {
-                    let txt = app.input_buffer.clone() + "_";
-                    cell = Cell::from(txt);
+                    cell = Cell::from(app.input_buffer.clone());
                 }
-                cell = cell.style(
-                    Style::default()
-                        .fg(Color::Black)
-                        .bg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                );
+                cell = cell.style(app.theme.selection);
             }
             cell
         });
@@ -93,18 +133,25 @@ pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
     });
 
     let mut constraints = vec![Constraint::Length(ROWLABELW)];
-    constraints.extend(vec![
-        Constraint::Length(COLWIDTH);
-        max_col.saturating_sub(app.topleft.1)
-    ]);
+    constraints.extend(
+        shown_cols
+            .iter()
+            .map(|&c| Constraint::Length(app.col_width(c))),
+    );
 
     let table = Table::new(rows, constraints)
         .header(header)
         .block(
             Block::default()
-                .title("TUI Spreadsheet")
+                // [+] marks unsaved changes
+                .title(if app.is_dirty() {
+                    "TUI Spreadsheet [+]"
+                } else {
+                    "TUI Spreadsheet"
+                })
                 .borders(Borders::ALL),
         )
+        .style(app.theme.grid)
         .column_spacing(1);
 
     let mode_text = match app.mode {
@@ -113,11 +160,25 @@ pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
         Mode::Select(_, _) => "SELECT",
         Mode::ErrMsg(_) => "ERR",
         Mode::Graph(_, _) => "GRAPH",
+        Mode::Stats(_, _) => "STATS",
+        Mode::Help(_) => "HELP",
+        Mode::Messages(_) => "MESSAGES",
+        Mode::Recover => "RECOVER",
     };
 
+    // The formula bar is empty in Select mode, the status bar uses the room for the selection's aggregates
+    // An error of the line being edited also takes the room of the status bar
+    let status_width = if matches!(app.mode, Mode::Select(_, _)) || app.error.is_some() {
+        60
+    } else {
+        25
+    };
     let bottom_row = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)])
+        .constraints([
+            Constraint::Percentage(100 - status_width),
+            Constraint::Percentage(status_width),
+        ])
         .split(chunks[1]);
 
     let ip_op_bar = bottom_row[0];
@@ -127,18 +188,29 @@ pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
         Mode::Normal => get_formula(&app.db, (1000 * app.selected.1 + app.selected.0) as u32),
         Mode::Select(_, _) => "".to_string(),
         Mode::ErrMsg(msg) => msg.to_string(),
+        Mode::Recover => format!(
+            "Found unsaved changes of {} in {}, recover them? (y/n)",
+            app.file_name,
+            swap_path(&app.file_name)
+        ),
+        Mode::Insert | Mode::NormalCommand => {
+            // The terminal cursor marks the editing position in the formula bar
+            let col = app.input_buffer[..app.cursor_pos()].chars().count() as u16;
+            f.set_cursor(
+                ip_op_bar.x + col.min(ip_op_bar.width.saturating_sub(1)),
+                ip_op_bar.y,
+            );
+            app.input_buffer.clone()
+        }
         _ => app.input_buffer.clone() + "_",
     };
 
     f.render_widget(table, chunks[0]);
     match app.mode {
-        Mode::ErrMsg(_) => {
+        Mode::ErrMsg(_) | Mode::Recover => {
             f.render_widget(
-                Paragraph::new(Text::styled(
-                    format!("{}", ip_op_text),
-                    Style::default().fg(Color::Red),
-                ))
-                .alignment(Alignment::Left),
+                Paragraph::new(Text::styled(ip_op_text.clone(), app.theme.error))
+                    .alignment(Alignment::Left),
                 ip_op_bar,
             );
         }
@@ -149,45 +221,264 @@ pub fn render<B: Backend>(f: &mut Frame, app: &mut App) {
             );
         }
     }
-    f.render_widget(
-        Paragraph::new(format!(
-            "{}, {} | {}",
-            app.selected.0, app.selected.1, mode_text
+    let filter_text = if app.filter.is_some() {
+        "FILTER | "
+    } else {
+        ""
+    };
+    // Like the SUM and AVG formulas, the aggregates of a range with errors are errors
+    let aggregate_text = match (app.mode, app.selection_summary()) {
+        (Mode::Select(_, _), summary) if app.selection_errors() > 0 => format!(
+            "SUM #ERR | AVG #ERR | COUNT {} | ",
+            summary.map_or(0, |summary| summary.count)
+        ),
+        (Mode::Select(_, _), Some(summary)) => format!(
+            "SUM {} | AVG {} | COUNT {} | ",
+            short_number(summary.sum),
+            short_number(summary.mean),
+            summary.count
+        ),
+        (Mode::Select(_, _), None) => "COUNT 0 | ".to_string(),
+        _ => String::new(),
+    };
+    let status = match &app.error {
+        Some(error) => Paragraph::new(error.clone()).style(app.theme.error),
+        None => Paragraph::new(format!(
+            "{}{}, {} | {}{}",
+            aggregate_text, app.selected.0, app.selected.1, filter_text, mode_text
         ))
-        .alignment(Alignment::Right),
-        status_bar,
-    );
+        .style(app.theme.status_bar),
+    };
+    f.render_widget(status.alignment(Alignment::Right), status_bar);
 
-    if let Mode::Graph(_, _) = app.mode {
+    if let Mode::Help(scroll) = app.mode {
+        let lines = help_lines(&app.keymap);
+        render_text(f, chunks[0], app, "Help", &lines, scroll);
+    }
+
+    if let Mode::Messages(scroll) = app.mode {
+        render_text(f, chunks[0], app, "Messages", &app.messages, scroll);
+    }
+
+    if let Mode::Stats(_, _) = app.mode {
+        render_stats(f, centered_rect(60, 50, chunks[0]), app);
+    }
+
+    if let Mode::Graph(a, b) = app.mode {
         let graphing_area = centered_rect(60, 50, chunks[0]);
+        let block = Block::default()
+            .title(format!("{} (t: change type)", app.chart.title()))
+            .borders(Borders::ALL)
+            .style(app.theme.popup);
+
+        match app.chart {
+            ChartKind::Bar => {}
+            ChartKind::Line | ChartKind::Scatter => {
+                render_xy_chart(f, graphing_area, block, app, a, b);
+                return;
+            }
+            ChartKind::Sparkline => {
+                render_sparkline(f, graphing_area, block, app);
+                return;
+            }
+        }
 
         let data = extract_range_data(app);
         let data = transform_data_for_barchart(&data);
 
+        // Bars have the height of the absolute value, negative ones are drawn in the second color
         let bars: Vec<Bar> = data
             .iter()
-            .map(|(label, value)| {
+            .map(|&(label, height, value)| {
+                let color = app.theme.chart[if value < 0.0 { 1 } else { 0 }];
                 Bar::default()
-                    .value(*value)
-                    .label(Line::from(label.clone()))
-                    .text_value(format!("{value}"))
-                    .style(Style::default().fg(Color::Green))
-                    .value_style(Style::default().fg(Color::Black).bg(Color::Green))
+                    .value(height)
+                    .label(Line::from(label))
+                    .text_value(short_number(value))
+                    .style(Style::default().fg(color))
+                    .value_style(app.theme.bar_value().bg(color))
             })
             .collect();
 
         let bar_group = BarGroup::default().bars(&bars);
 
         let barchart = BarChart::default()
-            .block(
-                Block::default()
-                    .title("Bar Chart")
-                    .borders(Borders::ALL)
-                    .style(Style::default().bg(Color::Black)), // Set popup background color here
-            )
+            .block(block)
             .data(bar_group)
             .bar_width(5);
 
         f.render_widget(barchart, graphing_area);
     }
 }
+
+/// Draws a scrolled text in a popup over area, like the help screen
+fn render_text(f: &mut Frame, area: Rect, app: &App, title: &str, lines: &[String], scroll: u16) {
+    let text = Paragraph::new(lines.join("\n"))
+        .block(
+            Block::default()
+                .title(format!("{} (q: close, Up/Down: scroll)", title))
+                .borders(Borders::ALL),
+        )
+        .style(app.theme.popup)
+        .scroll((scroll, 0));
+    let area = centered_rect(80, 80, area);
+    f.render_widget(Clear, area);
+    f.render_widget(text, area);
+}
+
+/// Writes a number without trailing zeros, rounded to at most 2 decimals
+pub fn short_number(value: f32) -> String {
+    let s = format!("{:.2}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Draws the selection as a line or scatter chart, see ChartData for the series
+fn render_xy_chart(
+    f: &mut Frame,
+    area: Rect,
+    block: Block,
+    app: &App,
+    a: (usize, usize),
+    b: (usize, usize),
+) {
+    let data = ChartData::from_range(&app.db, a, b);
+    let ((x_min, x_max), (y_min, y_max)) = match data.bounds() {
+        Some(bounds) => bounds,
+        None => {
+            f.render_widget(Paragraph::new("No numbers to plot").block(block), area);
+            return;
+        }
+    };
+
+    let graph_type = if app.chart == ChartKind::Line {
+        GraphType::Line
+    } else {
+        GraphType::Scatter
+    };
+    let datasets = data
+        .series
+        .iter()
+        .zip(app.theme.chart.iter().cycle())
+        .map(|((name, points), &color)| {
+            Dataset::default()
+                .name(name.clone())
+                .marker(symbols::Marker::Braille)
+                .graph_type(graph_type)
+                .style(Style::default().fg(color))
+                .data(points)
+        })
+        .collect();
+
+    let labels = |low: f64, high: f64| {
+        [low, (low + high) / 2.0, high]
+            .iter()
+            .map(|v| Span::raw(format!("{:.2}", v)))
+            .collect()
+    };
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(
+            Axis::default()
+                .title(data.x_title.clone())
+                .bounds([x_min, x_max])
+                .labels(labels(x_min, x_max)),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([y_min, y_max])
+                .labels(labels(y_min, y_max)),
+        );
+
+    f.render_widget(chart, area);
+}
+
+/// Draws the selection as a sparkline, scaled between its smallest and largest value
+fn render_sparkline(f: &mut Frame, area: Rect, block: Block, app: &App) {
+    let values: Vec<f32> = extract_range_data(app).iter().map(|(_, v)| *v).collect();
+    let (min, max, scaled) = match sparkline_data(&values) {
+        Some(data) => data,
+        None => {
+            f.render_widget(Paragraph::new("No numbers to plot").block(block), area);
+            return;
+        }
+    };
+
+    let sparkline = Sparkline::default()
+        .block(block.title(format!("min {:.2}, max {:.2}", min, max)))
+        .data(&scaled)
+        .max(100)
+        .style(Style::default().fg(app.theme.chart[0]));
+
+    f.render_widget(sparkline, area);
+}
+
+/// Number of buckets of the histogram of the statistics popup
+const HISTOGRAM_BUCKETS: usize = 8;
+
+/// Draws the summary statistics of the selection next to a histogram of its values
+fn render_stats(f: &mut Frame, area: Rect, app: &App) {
+    let block = Block::default()
+        .title("Statistics (q: close)")
+        .borders(Borders::ALL)
+        .style(app.theme.popup);
+
+    let values: Vec<f32> = extract_range_data(app).iter().map(|(_, v)| *v).collect();
+    let summary = match Summary::from_values(&values) {
+        Some(summary) => summary,
+        None => {
+            f.render_widget(Paragraph::new("No numbers selected").block(block), area);
+            return;
+        }
+    };
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(24), Constraint::Min(0)])
+        .split(inner);
+
+    let errors = app.selection_errors();
+    let lines: Vec<Line> = [
+        ("Count", summary.count.to_string()),
+        ("Sum", format!("{:.2}", summary.sum)),
+        ("Mean", format!("{:.2}", summary.mean)),
+        ("Median", format!("{:.2}", summary.median)),
+        ("Stdev", format!("{:.2}", summary.stdev)),
+        ("Min", format!("{:.2}", summary.min)),
+        ("Max", format!("{:.2}", summary.max)),
+        ("Errors", errors.to_string()),
+    ]
+    .into_iter()
+    // Error cells are left out of the statistics, their number is only shown if there are some
+    .filter(|&(name, _)| name != "Errors" || errors > 0)
+    .map(|(name, value)| Line::from(format!("{:<8}{:>14}", name, value)))
+    .collect();
+    f.render_widget(Paragraph::new(lines), halves[0]);
+
+    // Bars are labelled with the lower bound of their bucket
+    let buckets = histogram(&values, HISTOGRAM_BUCKETS);
+    let bars: Vec<Bar> = buckets
+        .iter()
+        .map(|(low, count)| {
+            Bar::default()
+                .value(*count)
+                .label(Line::from(format!("{:.1}", low)))
+                .style(Style::default().fg(app.theme.chart[0]))
+                .value_style(app.theme.bar_value())
+        })
+        .collect();
+    let bar_width = (halves[1].width / HISTOGRAM_BUCKETS as u16)
+        .saturating_sub(1)
+        .max(1);
+    let barchart = BarChart::default()
+        .block(Block::default().title("Histogram").borders(Borders::LEFT))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width);
+    f.render_widget(barchart, halves[1]);
+}
@@ -525,6 +525,27 @@ pub fn parse(s: &str) -> Response {
         }
     }
 
+    if let Some(range) = s.strip_prefix("clear ") {
+        let mut ret1 = String::new();
+        let mut ret2 = String::new();
+        if ib(valid_cell3(range)) {
+            returns.status = 0;
+            returns.func = 21;
+            returns.target = valid_cell3(range);
+            returns.arg1 = returns.target;
+            returns.arg_type = 2;
+        } else if valid_range(range, &mut ret1, &mut ret2) == 1 {
+            returns.status = 0;
+            returns.func = 21;
+            returns.target = valid_cell3(&ret1);
+            returns.arg1 = valid_cell3(&ret2);
+            returns.arg_type = 2;
+        } else {
+            returns.status = 1;
+        }
+        return returns;
+    }
+
     let mut idx = 0;
     while idx < length {
         if s.as_bytes()[idx] == b'=' {
@@ -562,6 +583,15 @@ pub fn parse(s: &str) -> Response {
             count += 1;
         }
 
+        // Command: clear, written as A1=
+        if remaining.is_empty() {
+            returns.status = 0;
+            returns.func = 21;
+            returns.arg1 = returns.target;
+            returns.arg_type = 2;
+            return returns;
+        }
+
         if count == remaining.len() {
             if valid_integer(remaining) {
                 returns.status = 0;
@@ -683,6 +713,41 @@ mod tests {
         );
         let str2 = "A1=MAX(Z1:X1)";
     }
+    #[test]
+    fn test_clear() {
+        let ret = parse("B2=");
+        assert!(
+            ret == Response {
+                status: 0,
+                func: 21,
+                target: 2002,
+                arg1: 2002,
+                arg2: 0,
+                arg_type: 2
+            },
+            "r = {:?}",
+            ret
+        );
+
+        let ret = parse("clear A1:B5");
+        assert!(
+            ret == Response {
+                status: 0,
+                func: 21,
+                target: 1001,
+                arg1: 2005,
+                arg2: 0,
+                arg_type: 2
+            },
+            "r = {:?}",
+            ret
+        );
+
+        assert!(parse("clear C1").func == 21);
+        assert!(parse("clear B5:A1").status == 1);
+        assert!(parse("clear").status == 1);
+    }
+
     #[test]
     fn test_2() {
         let k1 = valid_row("abcd");
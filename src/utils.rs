@@ -1,6 +1,11 @@
-use crate::database::{cell::CellData, range::DependencyNums, Database};
+use crate::database::{
+    cell::CellData,
+    range::{DependencyData, DependencyNums},
+    Database,
+};
 use crate::display::{self, generate_column_label};
-use crate::extensions::app::{App, Mode};
+use crate::extensions::app::App;
+use crate::parser::Response;
 use csv::Writer;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use std::collections::HashMap;
@@ -79,44 +84,101 @@ pub fn topological_sort(db: &Database, start: u32) -> Result<Vec<u32>, ()> {
     Ok(result)
 }
 
-// Check: try to use this function
-// pub fn rem_dep(db: &mut Database, dep: Option<DependencyData>, cell_idx: u32) {
-//     match dep {
-//         Some(dep) => {
-//             if (dep.get_oper() <= 6) | (dep.get_oper() == 12) {
-//                 match dep.get_pre() {
-//                     DependencyNums::U32(u) => db.rem_dep_point(u, cell_idx),
-//                     _ => {}
-//                 }
-//                 match dep.get_post() {
-//                     DependencyNums::U32(u) => db.rem_dep_point(u, cell_idx),
-//                     _ => {}
-//                 }
-//             } else {
-//                 db.rem_dep_range(cell_idx, dep.clone());
-//             };
-//         }
-//         None => {}
-//     };
-// }
+///function topological_sort_all performs a single
+///topological sort starting from every node in starts,
+///so that cells shared by several starts are evaluated once
+///
+pub fn topological_sort_all(db: &Database, starts: &[u32]) -> Result<Vec<u32>, ()> {
+    let mut visited = HashMap::new();
+    let mut result = Vec::new();
+
+    for &start in starts {
+        if dfs(db, start, &mut visited, &mut result) {
+            return Err(());
+        }
+    }
+
+    result.reverse();
+    Ok(result)
+}
+
+///function rem_dep unregisters the dependency dep of
+///cell_idx from the point or range dependency store
+///
+pub fn rem_dep(db: &mut Database, dep: Option<DependencyData>, cell_idx: u32) {
+    if let Some(dep) = dep {
+        if (dep.get_oper() <= 6) | (dep.get_oper() == 12) {
+            if let DependencyNums::U32(u) = dep.get_pre() {
+                db.rem_dep_point(u, cell_idx);
+            }
+            if let DependencyNums::U32(u) = dep.get_post() {
+                db.rem_dep_point(u, cell_idx);
+            }
+        } else {
+            db.rem_dep_range(cell_idx, dep);
+        }
+    }
+}
+
+///function dep_to_response builds the parser Response
+///that assigns the formula dep to cell_idx
+///returns None if dep cannot be written as a formula
+///
+pub fn dep_to_response(cell_idx: u32, dep: DependencyData) -> Option<Response> {
+    let mut r = Response {
+        func: dep.get_oper() as i32,
+        target: (cell_idx + 1001) as i32,
+        ..Default::default()
+    };
+
+    match dep.get_pre() {
+        DependencyNums::U32(u) => {
+            r.arg1 = (u + 1001) as i32;
+            r.arg_type |= 2;
+        }
+        DependencyNums::I32(i) => r.arg1 = i,
+        DependencyNums::F32(_) => return None,
+    }
+
+    // Formulas with a single argument store a dummy post
+    if dep.get_oper() == 2 || dep.get_oper() == 12 {
+        return Some(r);
+    }
+
+    match dep.get_post() {
+        DependencyNums::U32(u) => {
+            r.arg2 = (u + 1001) as i32;
+            r.arg_type |= 1;
+        }
+        DependencyNums::I32(i) => r.arg2 = i,
+        DependencyNums::F32(_) => return None,
+    }
+
+    Some(r)
+}
 
 #[cfg(not(tarpaulin_include))]
 
 ///function save_to_csv is used to save
-///the current state of the Database into a CSV file
+///the current state of the Database into a CSV file,
+///formatted writes values in their number format
 ///
-pub fn save_to_csv(db: &Database, path: &str) -> Result<(), Box<dyn Error>> {
+pub fn save_to_csv(db: &Database, path: &str, formatted: bool) -> Result<(), Box<dyn Error>> {
     // Prepare the 2D vector
     let mut table: Vec<Vec<String>> =
         vec![vec![String::new(); (db.num_cols + 1) as usize]; (db.num_rows + 1) as usize];
 
     for row in 0..db.num_rows {
         for col in 0..db.num_cols {
-            match db.get((1000 * col + row) as u32) {
-                Ok(data) => {
-                    table[row as usize][col as usize] = data.to_string();
+            // Empty cells are written as empty fields
+            let cell_idx = (1000 * col + row) as u32;
+            if let Ok(cell) = db.get_cell(cell_idx) {
+                if let Ok(data) = cell.get_data() {
+                    table[row as usize][col as usize] = match db.get_format(cell_idx) {
+                        Some(format) if formatted => format.apply(data),
+                        _ => data.to_string(),
+                    };
                 }
-                Err(_) => {}
             }
         }
     }
@@ -152,13 +214,9 @@ pub fn load_from_csv(path: &str) -> Result<Database, Box<dyn Error>> {
             }
 
             if let Ok(int_val) = value.parse::<i32>() {
-                if int_val != 0 {
-                    let _ = db.set_int((1000 * col_idx + row_idx) as u32, int_val);
-                }
+                let _ = db.set_int((1000 * col_idx + row_idx) as u32, int_val);
             } else if let Ok(float_val) = value.parse::<f32>() {
-                if float_val != 0.0 {
-                    let _ = db.set_float((1000 * col_idx + row_idx) as u32, float_val);
-                }
+                let _ = db.set_float((1000 * col_idx + row_idx) as u32, float_val);
             } else {
                 continue; // skip unparseable
             };
@@ -294,16 +352,38 @@ pub fn get_formula(db: &Database, cell_idx: u32) -> String {
     }
 }
 
+///function cell_text gives the text that sets a cell back to its
+///contents when entered: its formula, or its value in full precision
+///(unlike get_formula, which writes floats with 2 decimals)
+///an error constant gives "#ERR", an empty cell an empty string
+///
+pub fn cell_text(db: &Database, cell_idx: u32) -> String {
+    let cell = match db.get_cell(cell_idx) {
+        Ok(cell) => cell,
+        Err(_) => return String::new(),
+    };
+
+    if cell.has_dep() {
+        return get_formula(db, cell_idx);
+    }
+
+    match cell.get_data() {
+        Ok(CellData::IntData(i)) => i.to_string(),
+        Ok(CellData::FloatData(f)) => f.to_string(),
+        Ok(CellData::Empty) => String::new(),
+        Err(()) => "#ERR".to_string(),
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 
 ///function extract_range_data extracts numeric
-///data from a rectangular range in a spreadsheet
+///data from the selected range of the spreadsheet
+///(see App::selection), error cells are left out
+///like empty cells, see App::selection_errors
 ///
 pub fn extract_range_data(app: &App) -> Vec<(String, f32)> {
-    if let Mode::Graph((r1, c1), (r2, c2)) = app.mode {
-        let (row_low, row_high) = if r1 <= r2 { (r1, r2) } else { (r2, r1) };
-        let (col_low, col_high) = if c1 <= c2 { (c1, c2) } else { (c2, c1) };
-
+    if let Some(((row_low, col_low), (row_high, col_high))) = app.selection() {
         let mut res: Vec<(String, f32)> =
             Vec::with_capacity((row_high - row_low + 1) * (col_high - col_low + 1));
 
@@ -321,10 +401,9 @@ pub fn extract_range_data(app: &App) -> Vec<(String, f32)> {
                         CellData::FloatData(f) => {
                             res.push((cell_label, *f));
                         }
+                        CellData::Empty => {}
                     },
-                    Err(_) => {
-                        res.push((cell_label, 0f32));
-                    }
+                    Err(_) => {}
                 }
             }
         }
@@ -361,14 +440,30 @@ pub fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 
 #[cfg(not(tarpaulin_include))]
 ///function transform_data_for_barchart
-///prepares data for use in a bar chart
+///prepares data for use in a bar chart: the height of a bar is
+///the absolute value in hundredths, so fractions are not rounded away,
+///and the value itself is kept for its text and its sign
 ///
-pub fn transform_data_for_barchart(data: &Vec<(String, f32)>) -> Vec<(&str, u64)> {
+pub fn transform_data_for_barchart(data: &[(String, f32)]) -> Vec<(&str, u64, f32)> {
     data.iter()
         .map(|(label, value)| {
             let label_slice: &str = label.as_str();
-            let value_u64 = value.round() as u64;
-            (label_slice, value_u64)
+            let height = (value.abs() * 100.0).round() as u64;
+            (label_slice, height, *value)
         })
         .collect()
 }
+
+/// Runs a command like "A1=B1+1" on db, for the tests of the modules editing the sheet
+/// Returns the error code of the evaluator, 0 on success
+#[cfg(test)]
+pub fn run_command(db: &mut Database, cmd: &str) -> i32 {
+    let (mut tmp1, mut tmp2, mut tmp3): (u32, bool, bool) = (0, true, true);
+    crate::evaluator::evaluator(
+        crate::parser::parse(cmd),
+        db,
+        &mut tmp1,
+        &mut tmp2,
+        &mut tmp3,
+    )
+}